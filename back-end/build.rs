@@ -1,6 +1,14 @@
-use std::{env, fs, path::Path};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::{env, fs, path::Path, path::PathBuf};
 
 const BUNDLED_DATA_FILE_ID: &str = "1OVsIizFF1zZWNfoLiX5gzkzjNaaUbQET";
+// folders of bundled_data packed into the compressed bundle
+const BUNDLED_TREES: &[&str] = &["arc_config", "arc_mods", "skyline", "save", "ssbu_settings"];
+// kept in the repository instead of the downloaded data, packed as a tree of the same name
+const INPUT_PROFILES_TREE: &str = "input_profiles";
+const COMPRESSION_LEVEL: i32 = 15;
 
 fn main() {
     let out_dir = env::var("OUT_DIR").unwrap();
@@ -44,6 +52,76 @@ fn main() {
         }
     }
 
+    // every file is its own zstd frame so any subtree can be extracted without decompressing the rest
+    println!("Compressing data files...");
+    let mut files = vec![];
+    for tree in BUNDLED_TREES {
+        collect_bundled_files(&Path::new("bundled_data").join(tree), tree, &mut files);
+    }
+    collect_bundled_files(
+        Path::new(INPUT_PROFILES_TREE),
+        INPUT_PROFILES_TREE,
+        &mut files,
+    );
+    files.sort();
+    let mut bundle = fs::File::create(Path::new(&out_dir).join("bundled_data.bin"))
+        .expect("Unable to create bundled data archive");
+    let mut index = String::from("&[\n");
+    let mut tree_hashers: BTreeMap<String, Sha256> = BTreeMap::new();
+    let mut offset = 0;
+    for (path, file_path) in files {
+        let contents = fs::read(file_path).expect("Unable to read bundled data file");
+        let sha256 = format!("{:x}", Sha256::digest(&contents));
+        let tree = path.split('/').next().unwrap_or_default().to_string();
+        tree_hashers
+            .entry(tree)
+            .or_default()
+            .update(format!("{} {}\n", path, sha256));
+        let compressed = zstd::encode_all(contents.as_slice(), COMPRESSION_LEVEL)
+            .expect("Unable to compress bundled data file");
+        bundle
+            .write_all(&compressed)
+            .expect("Unable to write bundled data archive");
+        index.push_str(&format!(
+            "    BundledFile {{ path: {:?}, offset: {}, compressed_size: {}, size: {}, sha256: {:?} }},\n",
+            path,
+            offset,
+            compressed.len(),
+            contents.len(),
+            sha256
+        ));
+        offset += compressed.len();
+    }
+    index.push_str("]\n");
+    fs::write(Path::new(&out_dir).join("bundled_index.rs"), index)
+        .expect("Unable to write bundled data index");
+
+    // one hash per tree over every path and file hash, changes whenever a file of the tree does
+    let mut hashes = String::from("&[\n");
+    for (tree, hasher) in tree_hashers {
+        hashes.push_str(&format!(
+            "    ({:?}, {:?}),\n",
+            tree,
+            format!("{:x}", hasher.finalize())
+        ));
+    }
+    hashes.push_str("]\n");
+    fs::write(Path::new(&out_dir).join("bundled_hashes.rs"), hashes)
+        .expect("Unable to write bundled data hashes");
+
     println!("Data files are ready to be bundled into application!");
     tauri_build::build()
 }
+
+// paths are relative to bundled_data and always use forward slashes
+fn collect_bundled_files(dir: &Path, relative_path: &str, files: &mut Vec<(String, PathBuf)>) {
+    for entry in fs::read_dir(dir).expect("Unable to read bundled data directory") {
+        let entry = entry.unwrap();
+        let entry_path = format!("{}/{}", relative_path, entry.file_name().to_string_lossy());
+        if entry.file_type().unwrap().is_dir() {
+            collect_bundled_files(&entry.path(), &entry_path, files);
+        } else {
+            files.push((entry_path, entry.path()));
+        }
+    }
+}
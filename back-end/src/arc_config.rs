@@ -0,0 +1,147 @@
+use crate::config::OptimizerConfig;
+use crate::profile::UserProfile;
+use crate::utils::{io_error, write_atomic};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use toml_edit::{value, Array, DocumentMut, Item};
+
+const ARC_CONFIG_FILE: &str = "config.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoggingLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LoggingLevel {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LoggingLevel::Error => "Error",
+            LoggingLevel::Warn => "Warn",
+            LoggingLevel::Info => "Info",
+            LoggingLevel::Debug => "Debug",
+            LoggingLevel::Trace => "Trace",
+        }
+    }
+
+    fn parse(level: &str) -> Option<Self> {
+        [
+            LoggingLevel::Error,
+            LoggingLevel::Warn,
+            LoggingLevel::Info,
+            LoggingLevel::Debug,
+            LoggingLevel::Trace,
+        ]
+        .into_iter()
+        .find(|l| l.as_str().eq_ignore_ascii_case(level))
+    }
+}
+
+// options missing from the file are None, and None leaves the key untouched when saving
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArcConfig {
+    pub workspace: Option<String>,
+    pub extra_paths: Option<Vec<String>>,
+    pub logging_level: Option<LoggingLevel>,
+    pub region: Option<String>,
+    pub auto_update: Option<bool>,
+    pub beta_updates: Option<bool>,
+    pub skip_title_scene: Option<bool>,
+    pub debug: Option<bool>,
+}
+
+fn get_arc_config_path(
+    config: &OptimizerConfig,
+    user_profile: &UserProfile,
+) -> io::Result<PathBuf> {
+    Ok(config
+        .get_arc_config_folder(user_profile)?
+        .join(ARC_CONFIG_FILE))
+}
+
+fn load_document(config: &OptimizerConfig, user_profile: &UserProfile) -> io::Result<DocumentMut> {
+    let path = get_arc_config_path(config, user_profile)?;
+    if !path.is_file() {
+        return Ok(DocumentMut::new());
+    }
+    fs::read_to_string(path.as_path())?
+        .parse::<DocumentMut>()
+        .map_err(|e| {
+            io_error!(
+                InvalidData,
+                "Unable to parse ARCropolis config {}: {}",
+                path.to_string_lossy(),
+                e
+            )
+        })
+}
+
+pub fn get_arc_config(
+    config: &OptimizerConfig,
+    user_profile: &UserProfile,
+) -> io::Result<ArcConfig> {
+    let document = load_document(config, user_profile)?;
+    let get_str = |key: &str| document.get(key).and_then(|v| v.as_str()).map(String::from);
+    let get_bool = |key: &str| document.get(key).and_then(|v| v.as_bool());
+    Ok(ArcConfig {
+        workspace: get_str("workspace"),
+        extra_paths: document
+            .get("extra_paths")
+            .and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|p| p.as_str())
+                    .map(String::from)
+                    .collect()
+            }),
+        logging_level: get_str("logging_level").and_then(|l| LoggingLevel::parse(&l)),
+        region: get_str("region"),
+        auto_update: get_bool("auto_update"),
+        beta_updates: get_bool("beta_updates"),
+        skip_title_scene: get_bool("skip_title_scene"),
+        debug: get_bool("debug"),
+    })
+}
+
+// only the typed options are written, every other key and comment in the file is kept as is
+pub fn set_arc_config(
+    config: &OptimizerConfig,
+    user_profile: &UserProfile,
+    arc_config: &ArcConfig,
+) -> io::Result<()> {
+    let mut document = load_document(config, user_profile)?;
+    let mut set = |key: &str, item: Option<Item>| {
+        if let Some(item) = item {
+            document[key] = item;
+        }
+    };
+    set("workspace", arc_config.workspace.as_deref().map(value));
+    set(
+        "extra_paths",
+        arc_config
+            .extra_paths
+            .as_ref()
+            .map(|p| value(p.iter().collect::<Array>())),
+    );
+    set(
+        "logging_level",
+        arc_config.logging_level.map(|l| value(l.as_str())),
+    );
+    set("region", arc_config.region.as_deref().map(value));
+    set("auto_update", arc_config.auto_update.map(value));
+    set("beta_updates", arc_config.beta_updates.map(value));
+    set("skip_title_scene", arc_config.skip_title_scene.map(value));
+    set("debug", arc_config.debug.map(value));
+
+    let path = get_arc_config_path(config, user_profile)?;
+    log::info!("Saving ARCropolis config: {}", path.to_string_lossy());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_atomic(path.as_path(), document.to_string().as_bytes())
+}
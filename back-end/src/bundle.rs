@@ -0,0 +1,78 @@
+use std::io::{self, Read};
+use std::path::Path;
+
+pub const ARC_CONFIG_TREE: &str = "arc_config";
+pub const ARC_MODS_TREE: &str = "arc_mods";
+pub const SKYLINE_TREE: &str = "skyline";
+pub const SAVE_TREE: &str = "save";
+pub const SSBU_SETTINGS_TREE: &str = "ssbu_settings";
+pub const INPUT_PROFILES_TREE: &str = "input_profiles";
+
+// concatenated zstd frames, one per bundled file
+static BUNDLE_DATA: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/bundled_data.bin"));
+// sorted by path so every tree and folder is a contiguous range of the index
+static BUNDLE_INDEX: &[BundledFile] = include!(concat!(env!("OUT_DIR"), "/bundled_index.rs"));
+// (tree, content hash of the tree)
+static BUNDLE_HASHES: &[(&str, &str)] = include!(concat!(env!("OUT_DIR"), "/bundled_hashes.rs"));
+
+pub const BUNDLE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug)]
+pub struct BundledFile {
+    // relative to bundled_data, always with forward slashes
+    pub path: &'static str,
+    offset: usize,
+    compressed_size: usize,
+    pub size: u64,
+    pub sha256: &'static str,
+}
+
+impl BundledFile {
+    pub fn path_in(&self, root: &str) -> &'static Path {
+        Path::new(
+            self.path
+                .strip_prefix(root)
+                .map(|p| p.trim_start_matches('/'))
+                .unwrap_or(self.path),
+        )
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        self.path.rsplit('/').next().unwrap_or(self.path)
+    }
+
+    pub fn reader(&self) -> io::Result<impl Read> {
+        zstd::stream::read::Decoder::with_buffer(
+            &BUNDLE_DATA[self.offset..self.offset + self.compressed_size],
+        )
+    }
+
+    pub fn contents(&self) -> io::Result<Vec<u8>> {
+        let mut contents = Vec::with_capacity(self.size as usize);
+        self.reader()?.read_to_end(&mut contents)?;
+        Ok(contents)
+    }
+}
+
+fn to_bundle_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// every file under root, which can be a whole tree or any folder inside one
+pub fn files(root: &Path) -> &'static [BundledFile] {
+    let prefix = format!("{}/", to_bundle_path(root));
+    let start = BUNDLE_INDEX.partition_point(|f| f.path < prefix.as_str());
+    let len = BUNDLE_INDEX[start..].partition_point(|f| f.path.starts_with(&prefix));
+    &BUNDLE_INDEX[start..start + len]
+}
+
+pub fn tree_hash(tree: &str) -> &'static str {
+    BUNDLE_HASHES
+        .iter()
+        .find(|(t, _)| *t == tree)
+        .map(|(_, h)| *h)
+        .unwrap_or_default()
+}
@@ -0,0 +1,112 @@
+use crate::packs::PackTarget;
+use crate::utils::io_error;
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum BundledTree {
+    Skyline,
+    ArcMods,
+}
+
+impl BundledTree {
+    pub fn pack_target(&self) -> PackTarget {
+        match self {
+            BundledTree::Skyline => PackTarget::SdmcSkyline,
+            BundledTree::ArcMods => PackTarget::ArcMods,
+        }
+    }
+}
+
+// paths are relative to the root of their bundled tree, anything in a tree
+// that isn't listed here (skyline, arcropolis...) is always installed
+#[derive(Debug, Clone, Serialize)]
+pub struct BundledMod {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub paths: &'static [(BundledTree, &'static str)],
+}
+
+pub static BUNDLED_MODS: &[BundledMod] = &[
+    BundledMod {
+        id: "hollow_bastion",
+        name: "Hollow Bastion",
+        description: "Eternal Heart version of Hollow Bastion to maximize performance on the stage",
+        paths: &[(BundledTree::ArcMods, "hollow_bastion_eternal_heart")],
+    },
+    BundledMod {
+        id: "css_preserve",
+        name: "CSS Preserve",
+        description: "Keeps the same character selected after a set in LDN mode or offline",
+        paths: &[(BundledTree::ArcMods, "css_preserve")],
+    },
+    BundledMod {
+        id: "one_slot_effects",
+        name: "One Slot Effects",
+        description: "Allows effect mods to be used on multiple slots simultaneously",
+        paths: &[(BundledTree::ArcMods, "one_slot_effects")],
+    },
+    BundledMod {
+        id: "training_modpack",
+        name: "Training Modpack",
+        description: "Extra training mode options such as hitbox visualization and save states",
+        paths: &[(BundledTree::ArcMods, "training_modpack")],
+    },
+    BundledMod {
+        id: "latency_slider",
+        name: "Latency Slider",
+        description:
+            "Reduces the added online latency and allows changing the FPS from within the emulator",
+        paths: &[(BundledTree::ArcMods, "latency_slider")],
+    },
+];
+
+pub fn get_bundled_mod(id: &str) -> io::Result<&'static BundledMod> {
+    BUNDLED_MODS.iter().find(|m| m.id == id).ok_or(io_error!(
+        NotFound,
+        "Unknown bundled mod: {}",
+        id
+    ))
+}
+
+impl BundledMod {
+    pub fn contains(&self, tree: BundledTree, path: &Path) -> bool {
+        self.paths
+            .iter()
+            .any(|(t, p)| *t == tree && path.starts_with(p))
+    }
+}
+
+// paths that belong to no bundled mod are always selected
+pub fn is_path_selected(tree: BundledTree, path: &Path, selected_mods: &[String]) -> bool {
+    BUNDLED_MODS
+        .iter()
+        .filter(|m| m.contains(tree, path))
+        .all(|m| selected_mods.iter().any(|id| id == m.id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle;
+
+    #[test]
+    fn every_path_is_bundled() {
+        for bundled_mod in BUNDLED_MODS {
+            for (tree, path) in bundled_mod.paths {
+                let root = tree.pack_target().embedded_tree();
+                assert!(
+                    bundle::files(Path::new(root))
+                        .iter()
+                        .any(|f| f.path_in(root).starts_with(path)),
+                    "Bundled mod {} is missing {}/{}",
+                    bundled_mod.id,
+                    root,
+                    path
+                );
+            }
+        }
+    }
+}
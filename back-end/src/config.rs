@@ -1,5 +1,10 @@
+use crate::optimizer::OptimizationReport;
+use crate::packs::{ContentVersion, PackSecuritySettings, PackTarget};
 use crate::profile::{self, UserProfile};
+use crate::quarantine::QuarantineRetention;
 use crate::utils::io_error;
+use crate::verify::VerificationIssue;
+use crate::web_service::WebServiceSettings;
 use ini::Ini;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -13,9 +18,11 @@ pub const SSBU_TITLE_ID: &str = "01006A800016E000";
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Optimization {
-    Settings,
-    Mods,
+    Settings(String),
+    Mods(Vec<String>),
     Save,
+    // installs every bundled input profile, optionally binding one to player 1 for SSBU
+    Input(Option<String>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -24,18 +31,42 @@ pub enum AdvancedOption {
     CleanArc,
 }
 
+// the content each optimization installs, used to tell when it is outdated
+pub const SETTINGS_TARGETS: &[PackTarget] = &[PackTarget::ConfigCustom];
+pub const MODS_TARGETS: &[PackTarget] = &[
+    PackTarget::SdmcSkyline,
+    PackTarget::ArcMods,
+    PackTarget::ArcConfig,
+];
+pub const SAVE_TARGETS: &[PackTarget] = &[PackTarget::Save];
+pub const INPUT_TARGETS: &[PackTarget] = &[PackTarget::ConfigInput];
+
+impl Optimization {
+    pub fn targets(&self) -> &'static [PackTarget] {
+        match self {
+            Optimization::Settings(_) => SETTINGS_TARGETS,
+            Optimization::Mods(_) => MODS_TARGETS,
+            Optimization::Save => SAVE_TARGETS,
+            Optimization::Input(_) => INPUT_TARGETS,
+        }
+    }
+}
+
 impl std::fmt::Display for Optimization {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Optimization::Settings => write!(f, "Settings"),
-            Optimization::Mods => write!(f, "Mods"),
+            Optimization::Settings(preset) => write!(f, "Settings ({})", preset),
+            Optimization::Mods(mods) => write!(f, "Mods ({})", mods.join(", ")),
             Optimization::Save => write!(f, "Save"),
+            Optimization::Input(Some(profile)) => write!(f, "Input ({})", profile),
+            Optimization::Input(None) => write!(f, "Input"),
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OptimizerConfig {
+    pub app_data_folder: PathBuf,
     pub local_data: LocalPersistantData,
     pub user_profiles: Vec<UserProfile>,
     pub emu_filesystem: EmuFileSystem,
@@ -49,6 +80,9 @@ impl OptimizerConfig {
         let data_dir = path_resolver
             .data_dir()
             .expect("Unable to get data directory");
+        let app_data_folder = path_resolver
+            .app_data_dir()
+            .expect("Unable to find app data directory");
         let default_emu_folder = data_dir.join(DEFAULT_EMU);
         let mut local_data = LocalPersistantData::load(path_resolver);
         let emu_folder =
@@ -69,6 +103,7 @@ impl OptimizerConfig {
                     .filter(|u| user_profiles.contains(u))
                     .or_else(|| user_profiles.first().cloned());
                 OptimizerConfig {
+                    app_data_folder,
                     local_data,
                     user_profiles,
                     emu_filesystem,
@@ -78,6 +113,7 @@ impl OptimizerConfig {
                 local_data.emu_folder = None;
                 local_data.selected_user_profile = None;
                 OptimizerConfig {
+                    app_data_folder,
                     local_data,
                     user_profiles: vec![],
                     emu_filesystem,
@@ -107,6 +143,59 @@ impl OptimizerConfig {
             .join(SSBU_TITLE_ID))
     }
 
+    // bundled settings as last applied, used as the base when merging user changes
+    pub fn get_settings_base_folder(&self) -> PathBuf {
+        self.app_data_folder
+            .join("settings_base")
+            .join(self.get_emulator_name())
+    }
+
+    pub fn get_custom_settings_folder(&self) -> io::Result<PathBuf> {
+        Ok(self
+            .emu_filesystem
+            .config_folder
+            .as_ref()
+            .ok_or(io_error!(NotFound, "Emulator config folder not found"))?
+            .join("custom"))
+    }
+
+    // SSBU's per-game config
+    pub fn get_game_settings_path(&self) -> io::Result<PathBuf> {
+        Ok(self
+            .get_custom_settings_folder()?
+            .join(format!("{}.ini", SSBU_TITLE_ID)))
+    }
+
+    pub fn get_input_profile_folder(&self) -> io::Result<PathBuf> {
+        Ok(self
+            .emu_filesystem
+            .config_folder
+            .as_ref()
+            .ok_or(io_error!(NotFound, "Emulator config folder not found"))?
+            .join("input"))
+    }
+
+    pub fn get_skyline_folder(&self) -> io::Result<PathBuf> {
+        Ok(self
+            .emu_filesystem
+            .sdmc_folder
+            .as_ref()
+            .ok_or(io_error!(NotFound, "Unable to find sdmc directory"))?
+            .join("atmosphere")
+            .join("contents")
+            .join(SSBU_TITLE_ID))
+    }
+
+    pub fn get_arc_mods_folder(&self) -> io::Result<PathBuf> {
+        Ok(self
+            .emu_filesystem
+            .sdmc_folder
+            .as_ref()
+            .ok_or(io_error!(NotFound, "Unable to find sdmc directory"))?
+            .join("ultimate")
+            .join("mods"))
+    }
+
     pub fn get_arc_config_folder(&self, user_profile: &UserProfile) -> io::Result<PathBuf> {
         let uuids = user_profile.get_uuid_arc_storage_strings();
         Ok(self
@@ -127,6 +216,53 @@ pub struct UserStatus {
     pub settings_optimized: bool,
     pub mods_optimized: bool,
     pub save_optimized: bool,
+    #[serde(default)]
+    pub settings_preset: Option<String>,
+    #[serde(default)]
+    pub installed_mods: Vec<String>,
+    // mods can be optimized but broken when the post-install verification found issues
+    #[serde(default)]
+    pub mods_issues: Vec<VerificationIssue>,
+    // the content each optimization was last applied with
+    #[serde(default)]
+    pub settings_version: Option<ContentVersion>,
+    #[serde(default)]
+    pub mods_version: Option<ContentVersion>,
+    #[serde(default)]
+    pub save_version: Option<ContentVersion>,
+    #[serde(default)]
+    pub input_optimized: bool,
+    #[serde(default)]
+    pub input_profile: Option<String>,
+    #[serde(default)]
+    pub input_version: Option<ContentVersion>,
+}
+
+impl UserStatus {
+    pub fn record(&mut self, optimization: Optimization, report: &OptimizationReport) {
+        match optimization {
+            Optimization::Settings(preset) => {
+                self.settings_optimized = true;
+                self.settings_preset = Some(preset);
+                self.settings_version = report.content_version.clone();
+            }
+            Optimization::Mods(selected_mods) => {
+                self.mods_optimized = true;
+                self.installed_mods = selected_mods;
+                self.mods_issues = report.verification.clone();
+                self.mods_version = report.content_version.clone();
+            }
+            Optimization::Save => {
+                self.save_optimized = true;
+                self.save_version = report.content_version.clone();
+            }
+            Optimization::Input(profile) => {
+                self.input_optimized = true;
+                self.input_profile = profile;
+                self.input_version = report.content_version.clone();
+            }
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -135,6 +271,12 @@ pub struct LocalPersistantData {
     pub selected_user_profile: Option<UserProfile>,
     #[serde(with = "vectorize")]
     pub user_statuses: HashMap<UserProfile, UserStatus>,
+    #[serde(default)]
+    pub web_service: WebServiceSettings,
+    #[serde(default)]
+    pub pack_security: PackSecuritySettings,
+    #[serde(default)]
+    pub quarantine_retention: QuarantineRetention,
 }
 
 impl LocalPersistantData {
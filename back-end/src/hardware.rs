@@ -0,0 +1,231 @@
+use crate::utils::set_per_game_setting;
+use ini::Ini;
+use serde::Serialize;
+use sysinfo::System;
+
+const HANDHELD_CPU_BRANDS: &[&str] = &["Custom APU 0405", "Custom APU 0932", "Ryzen Z1"];
+const HANDHELD_PRODUCT_NAMES: &[&str] = &["Jupiter", "Galileo", "ROG Ally", "83E1"];
+const LOW_MEMORY_MB: u64 = 8 * 1024;
+const LOW_CPU_THREADS: usize = 8;
+const LOW_CPU_CORES: usize = 4;
+
+const CPU_UNSAFE_OPTIONS: &[&str] = &[
+    "cpuopt_unsafe_reduce_fp_error",
+    "cpuopt_unsafe_ignore_standard_fpcr",
+    "cpuopt_unsafe_inaccurate_nan",
+    "cpuopt_unsafe_fastmem_check",
+    "cpuopt_unsafe_ignore_global_monitor",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HardwareInfo {
+    pub cpu_cores: usize,
+    pub cpu_threads: usize,
+    pub cpu_vendor: String,
+    pub cpu_model: String,
+    pub cpu_has_fma: bool,
+    pub total_memory_mb: u64,
+    pub on_battery: bool,
+    pub is_handheld: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingAdjustment {
+    pub section: String,
+    pub key: String,
+    pub value: String,
+    pub reason: String,
+}
+
+impl SettingAdjustment {
+    pub fn apply(&self, game_settings: &mut Ini) {
+        set_per_game_setting(game_settings, &self.section, &self.key, &self.value);
+    }
+}
+
+impl HardwareInfo {
+    pub fn detect() -> Self {
+        let mut system = System::new();
+        system.refresh_cpu();
+        system.refresh_memory();
+        let cpu = system.cpus().first();
+        let cpu_vendor = cpu.map(|c| c.vendor_id().trim().to_string());
+        let cpu_model = cpu.map(|c| c.brand().trim().to_string());
+        let cpu_threads = system.cpus().len();
+        let cpu_model = cpu_model.unwrap_or_default();
+        let is_handheld = HANDHELD_CPU_BRANDS.iter().any(|b| cpu_model.contains(b))
+            || read_product_name()
+                .map(|p| HANDHELD_PRODUCT_NAMES.iter().any(|n| p.starts_with(n)))
+                .unwrap_or(false);
+        let hardware_info = HardwareInfo {
+            cpu_cores: system.physical_core_count().unwrap_or(cpu_threads),
+            cpu_threads,
+            cpu_vendor: cpu_vendor.unwrap_or_default(),
+            cpu_model,
+            cpu_has_fma: cpu_has_fma(),
+            total_memory_mb: system.total_memory() / (1024 * 1024),
+            on_battery: is_on_battery(),
+            is_handheld,
+        };
+        log::info!("Detected hardware: {:#?}", hardware_info);
+        hardware_info
+    }
+
+    pub fn plan_adjustments(&self, game_settings: &Ini) -> Vec<SettingAdjustment> {
+        let mut adjustments = vec![];
+        let mut adjust = |section: &str, key: &str, value: &str, reason: String| {
+            adjustments.push(SettingAdjustment {
+                section: section.to_string(),
+                key: key.to_string(),
+                value: value.to_string(),
+                reason,
+            });
+        };
+
+        if self.cpu_threads <= LOW_CPU_THREADS {
+            adjust(
+                "Renderer",
+                "use_asynchronous_shaders",
+                "true",
+                format!(
+                    "{} CPU threads available, compiling shaders asynchronously avoids stutter",
+                    self.cpu_threads
+                ),
+            );
+        } else if self.is_handheld || self.on_battery {
+            adjust(
+                "Renderer",
+                "use_asynchronous_shaders",
+                "true",
+                "Running on a handheld or on battery, compiling shaders asynchronously avoids stutter".to_string(),
+            );
+        }
+
+        // resolution_setup: 1 = 0.75x, 2 = 1x
+        let resolution_cap = if self.total_memory_mb < LOW_MEMORY_MB {
+            Some((
+                1,
+                format!(
+                    "Only {} MB of RAM, likely shared with an integrated GPU",
+                    self.total_memory_mb
+                ),
+            ))
+        } else if self.is_handheld {
+            Some((
+                2,
+                "Handheld screens gain nothing above native resolution".to_string(),
+            ))
+        } else if self.on_battery {
+            Some((
+                2,
+                "Running on battery, higher resolutions drain power".to_string(),
+            ))
+        } else {
+            None
+        };
+        if let Some((cap, reason)) = resolution_cap {
+            let current = game_settings
+                .get_from(Some("Renderer"), "resolution_setup")
+                .and_then(|r| r.parse::<u32>().ok());
+            if current.map(|c| c > cap).unwrap_or(true) {
+                adjust("Renderer", "resolution_setup", &cap.to_string(), reason);
+            }
+        }
+
+        // cpu_accuracy: 0 = auto, 2 = unsafe
+        if self.cpu_cores < LOW_CPU_CORES || self.is_handheld {
+            let reason = format!(
+                "{} physical cores on {} ({}), unsafe CPU accuracy trades precision for speed",
+                self.cpu_cores, self.cpu_model, self.cpu_vendor
+            );
+            adjust("Cpu", "cpu_accuracy", "2", reason);
+            for option in CPU_UNSAFE_OPTIONS {
+                adjust(
+                    "Cpu",
+                    option,
+                    "true",
+                    "Enabled alongside unsafe CPU accuracy".to_string(),
+                );
+            }
+        } else {
+            adjust(
+                "Cpu",
+                "cpu_accuracy",
+                "0",
+                format!(
+                    "{} physical cores on {} ({}) can afford automatic CPU accuracy",
+                    self.cpu_cores, self.cpu_model, self.cpu_vendor
+                ),
+            );
+        }
+
+        if !self.cpu_has_fma {
+            adjust(
+                "Cpu",
+                "cpuopt_unsafe_unfuse_fma",
+                "true",
+                "CPU lacks FMA instructions, unfusing them is much faster".to_string(),
+            );
+        }
+
+        adjustments
+    }
+}
+
+fn cpu_has_fma() -> bool {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    return std::arch::is_x86_feature_detected!("fma");
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    return true;
+}
+
+fn read_product_name() -> Option<String> {
+    #[cfg(target_os = "linux")]
+    return std::fs::read_to_string("/sys/class/dmi/id/product_name")
+        .ok()
+        .map(|p| p.trim().to_string());
+    #[cfg(not(target_os = "linux"))]
+    return None;
+}
+
+#[cfg(target_os = "linux")]
+fn is_on_battery() -> bool {
+    match std::fs::read_dir("/sys/class/power_supply") {
+        Ok(supplies) => supplies.filter_map(|s| s.ok()).any(|s| {
+            let read = |f: &str| std::fs::read_to_string(s.path().join(f)).unwrap_or_default();
+            read("type").trim() == "Battery" && read("status").trim() == "Discharging"
+        }),
+        Err(_) => false,
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn is_on_battery() -> bool {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+    // BatteryStatus 1 means the battery is discharging
+    std::process::Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            "(Get-CimInstance Win32_Battery).BatteryStatus",
+        ])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim() == "1")
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "macos")]
+fn is_on_battery() -> bool {
+    std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).contains("'Battery Power'"))
+        .unwrap_or(false)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn is_on_battery() -> bool {
+    false
+}
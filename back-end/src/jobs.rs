@@ -0,0 +1,419 @@
+use crate::config::{AdvancedOption, Optimization};
+use crate::optimizer::{
+    self, OptimizationProgress, OptimizationReport, OptimizationStep, ProgressTracker,
+};
+use crate::packs::ContentSources;
+use crate::profile::UserProfile;
+use crate::utils::io_error;
+use crate::AppState;
+use serde::Serialize;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const JOB_EVENT: &str = "optimization-job";
+// progress events are sent at most this often, the last file of a step always is
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum JobState {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum StepState {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+    Skipped,
+    Cancelled,
+}
+
+// per-user steps have one result for every user of the job
+#[derive(Debug, Clone, Serialize)]
+pub struct StepResult {
+    pub step: String,
+    pub user_name: Option<String>,
+    pub state: StepState,
+    pub report: Option<OptimizationReport>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UserResult {
+    pub user_name: String,
+    pub state: JobState,
+    // the optimizations recorded for this user
+    pub applied: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub id: u64,
+    pub state: JobState,
+    pub steps: Vec<StepResult>,
+    pub users: Vec<UserResult>,
+    pub progress: Option<OptimizationProgress>,
+    pub error: Option<String>,
+}
+
+struct Job {
+    id: u64,
+    user_profiles: Vec<UserProfile>,
+    // every step with the index of its user for per-user steps
+    runs: Vec<(OptimizationStep, Option<usize>)>,
+    cancelled: Arc<AtomicBool>,
+}
+
+type JobStatuses = Arc<Mutex<BTreeMap<u64, (JobStatus, Arc<AtomicBool>)>>>;
+
+// finished jobs kept for get_job_status and list_jobs, the oldest are dropped first
+const FINISHED_JOBS_KEPT: usize = 20;
+
+fn prune_statuses(statuses: &mut BTreeMap<u64, (JobStatus, Arc<AtomicBool>)>) {
+    let finished: Vec<u64> = statuses
+        .iter()
+        .filter(|(_, (s, _))| !matches!(s.state, JobState::Queued | JobState::Running))
+        .map(|(id, _)| *id)
+        .collect();
+    for id in finished
+        .iter()
+        .take(finished.len().saturating_sub(FINISHED_JOBS_KEPT))
+    {
+        statuses.remove(id);
+    }
+}
+
+// jobs run one at a time on a single worker thread in the order they were queued
+pub struct JobQueue {
+    next_id: AtomicU64,
+    statuses: JobStatuses,
+    sender: Mutex<Sender<Job>>,
+}
+
+impl JobQueue {
+    pub fn start(app_handle: AppHandle) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let statuses = JobStatuses::default();
+        let worker_statuses = statuses.clone();
+        std::thread::spawn(move || run_worker(app_handle, receiver, worker_statuses));
+        JobQueue {
+            next_id: AtomicU64::new(1),
+            statuses,
+            sender: Mutex::new(sender),
+        }
+    }
+
+    // steps run in a fixed order regardless of the order they are given in
+    pub fn queue(
+        &self,
+        user_profiles: Vec<UserProfile>,
+        optimizations: Vec<(Optimization, Vec<AdvancedOption>)>,
+    ) -> io::Result<u64> {
+        if user_profiles.is_empty() {
+            return Err(io_error!(InvalidInput, "No users selected"));
+        }
+        let steps = optimizer::plan_steps(optimizations);
+        if steps.is_empty() {
+            return Err(io_error!(InvalidInput, "No optimizations selected"));
+        }
+        let mut runs = vec![];
+        for step in steps {
+            match step.is_per_user() {
+                true => runs.extend((0..user_profiles.len()).map(|u| (step.clone(), Some(u)))),
+                false => runs.push((step, None)),
+            }
+        }
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let status = JobStatus {
+            id,
+            state: JobState::Queued,
+            steps: runs
+                .iter()
+                .map(|(s, u)| StepResult {
+                    step: s.to_string(),
+                    user_name: u.map(|u| user_profiles[u].name.clone()),
+                    state: StepState::Pending,
+                    report: None,
+                    error: None,
+                })
+                .collect(),
+            users: user_profiles
+                .iter()
+                .map(|u| UserResult {
+                    user_name: u.name.clone(),
+                    state: JobState::Queued,
+                    applied: vec![],
+                })
+                .collect(),
+            progress: None,
+            error: None,
+        };
+        log::info!(
+            "Queueing job {} for {}: {}",
+            id,
+            user_profiles
+                .iter()
+                .map(|u| u.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            runs.iter()
+                .filter(|(_, u)| u.is_none() || *u == Some(0))
+                .map(|(s, _)| s.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        // inserted before sending so the worker always finds it
+        {
+            let mut statuses = self
+                .statuses
+                .lock()
+                .expect("Unable to acquire lock on job statuses");
+            prune_statuses(&mut statuses);
+            statuses.insert(id, (status, cancelled.clone()));
+        }
+        let sent = self
+            .sender
+            .lock()
+            .expect("Unable to acquire lock on job queue")
+            .send(Job {
+                id,
+                user_profiles,
+                runs,
+                cancelled,
+            });
+        if sent.is_err() {
+            self.statuses
+                .lock()
+                .expect("Unable to acquire lock on job statuses")
+                .remove(&id);
+            return Err(io_error!(BrokenPipe, "Optimization worker is not running"));
+        }
+        Ok(id)
+    }
+
+    pub fn get_status(&self, id: u64) -> io::Result<JobStatus> {
+        self.statuses
+            .lock()
+            .expect("Unable to acquire lock on job statuses")
+            .get(&id)
+            .map(|(s, _)| s.clone())
+            .ok_or(io_error!(NotFound, "Unknown job: {}", id))
+    }
+
+    pub fn list(&self) -> Vec<JobStatus> {
+        self.statuses
+            .lock()
+            .expect("Unable to acquire lock on job statuses")
+            .values()
+            .map(|(s, _)| s.clone())
+            .collect()
+    }
+
+    pub fn cancel(&self, id: u64) -> io::Result<()> {
+        let statuses = self
+            .statuses
+            .lock()
+            .expect("Unable to acquire lock on job statuses");
+        let (status, cancelled) =
+            statuses
+                .get(&id)
+                .ok_or(io_error!(NotFound, "Unknown job: {}", id))?;
+        match status.state {
+            JobState::Queued | JobState::Running => {
+                log::info!("Cancelling job {}", id);
+                cancelled.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+            state => Err(io_error!(
+                InvalidInput,
+                "Job {} has already finished: {:?}",
+                id,
+                state
+            )),
+        }
+    }
+}
+
+fn update_status(
+    app_handle: &AppHandle,
+    statuses: &JobStatuses,
+    id: u64,
+    update: impl FnOnce(&mut JobStatus),
+) {
+    let mut statuses = statuses
+        .lock()
+        .expect("Unable to acquire lock on job statuses");
+    if let Some((status, _)) = statuses.get_mut(&id) {
+        update(status);
+        if let Err(e) = app_handle.emit(JOB_EVENT, status.clone()) {
+            log::warn!("Unable to emit job status: {}", e);
+        }
+    }
+}
+
+// only kept for get_job_status, progress is streamed through its own event
+fn set_progress(statuses: &JobStatuses, id: u64, progress: &OptimizationProgress) {
+    let mut statuses = statuses
+        .lock()
+        .expect("Unable to acquire lock on job statuses");
+    if let Some((status, _)) = statuses.get_mut(&id) {
+        status.progress = Some(progress.clone());
+    }
+}
+
+fn run_job(app_handle: &AppHandle, statuses: &JobStatuses, job: Job) {
+    let state: tauri::State<AppState> = app_handle.state();
+    // work from a snapshot so the config lock is only held for the status update
+    let config = state.read_config().clone();
+    let sources = ContentSources::load(&config);
+    log::info!(
+        "Running job {} for {} users",
+        job.id,
+        job.user_profiles.len()
+    );
+
+    let last_progress: Cell<Option<Instant>> = Cell::new(None);
+    let on_progress = |p: &OptimizationProgress| {
+        let throttled = last_progress
+            .get()
+            .map(|t| t.elapsed() < PROGRESS_INTERVAL)
+            .unwrap_or(false);
+        if throttled && p.files_done < p.total_files {
+            return;
+        }
+        last_progress.set(Some(Instant::now()));
+        set_progress(statuses, job.id, p);
+        if let Err(e) = app_handle.emit(optimizer::PROGRESS_EVENT, p) {
+            log::warn!("Unable to emit optimization progress: {}", e);
+        }
+    };
+    let user_count = job.user_profiles.len();
+    let mut applied: Vec<Vec<(Optimization, OptimizationReport)>> = vec![vec![]; user_count];
+    // a failed per-user step only stops that user, a failed shared step stops everyone
+    let mut failed_users = vec![false; user_count];
+    let mut stopped = None;
+    let mut job_error = None;
+    for (i, (step, user)) in job.runs.iter().enumerate() {
+        let users: Vec<usize> = match user {
+            Some(u) => vec![*u],
+            None => (0..user_count).collect(),
+        };
+        let users: Vec<usize> = users.into_iter().filter(|u| !failed_users[*u]).collect();
+        if stopped.is_some() || users.is_empty() {
+            update_status(app_handle, statuses, job.id, |s| {
+                s.steps[i].state = StepState::Skipped
+            });
+            continue;
+        }
+        update_status(app_handle, statuses, job.id, |s| {
+            s.steps[i].state = StepState::Running
+        });
+        let user_profiles: Vec<UserProfile> = users
+            .iter()
+            .map(|u| job.user_profiles[*u].clone())
+            .collect();
+        log::info!("Running job {} step: {}", job.id, step);
+        let mut progress =
+            ProgressTracker::new(step.to_string(), &on_progress).with_cancellation(&job.cancelled);
+        let result = optimizer::run_step(&config, &sources, &user_profiles, step, &mut progress);
+        let (step_state, report, error) = match result {
+            Ok(report) => {
+                if let Some(optimization) = step.applied_optimization() {
+                    for u in users {
+                        applied[u].push((optimization.clone(), report.clone()));
+                    }
+                }
+                (StepState::Completed, Some(report), None)
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                log::info!("Job {} cancelled", job.id);
+                stopped = Some(JobState::Cancelled);
+                (StepState::Cancelled, None, None)
+            }
+            Err(e) => {
+                log::error!("Job {} step failed: {}", job.id, e);
+                match step.is_per_user() {
+                    true => users.iter().for_each(|u| failed_users[*u] = true),
+                    false => stopped = Some(JobState::Failed),
+                }
+                job_error.get_or_insert(e.to_string());
+                (StepState::Failed, None, Some(e.to_string()))
+            }
+        };
+        update_status(app_handle, statuses, job.id, |s| {
+            s.steps[i].state = step_state;
+            s.steps[i].report = report;
+            s.steps[i].error = error;
+        });
+    }
+
+    // steps that finished before a failure or cancellation are still recorded
+    if applied.iter().any(|a| !a.is_empty()) {
+        // saved under the lock, so saves from other jobs or commands can't land out of order
+        let mut config = state.write_config();
+        for (user_profile, applied) in job.user_profiles.iter().zip(applied.iter()) {
+            let user_status = config
+                .local_data
+                .user_statuses
+                .entry(user_profile.clone())
+                .or_default();
+            for (optimization, report) in applied {
+                user_status.record(optimization.clone(), report);
+            }
+        }
+        config.local_data.save(app_handle.path());
+    }
+    let job_state = match stopped {
+        Some(state) => state,
+        None if failed_users.contains(&true) => JobState::Failed,
+        None => JobState::Completed,
+    };
+    update_status(app_handle, statuses, job.id, |s| {
+        for (u, user) in s.users.iter_mut().enumerate() {
+            user.state = match (failed_users[u], stopped) {
+                (true, _) | (_, Some(JobState::Failed)) => JobState::Failed,
+                (_, Some(state)) => state,
+                (_, None) => JobState::Completed,
+            };
+            user.applied = applied[u].iter().map(|(o, _)| o.to_string()).collect();
+        }
+        s.state = job_state;
+        s.error = job_error;
+    });
+}
+
+fn run_worker(app_handle: AppHandle, receiver: Receiver<Job>, statuses: JobStatuses) {
+    for job in receiver {
+        if job.cancelled.load(Ordering::SeqCst) {
+            update_status(&app_handle, &statuses, job.id, |s| {
+                s.state = JobState::Cancelled;
+                for step in s.steps.iter_mut() {
+                    step.state = StepState::Cancelled;
+                }
+                for user in s.users.iter_mut() {
+                    user.state = JobState::Cancelled;
+                }
+            });
+            continue;
+        }
+        update_status(&app_handle, &statuses, job.id, |s| {
+            s.state = JobState::Running;
+            for user in s.users.iter_mut() {
+                user.state = JobState::Running;
+            }
+        });
+        run_job(&app_handle, &statuses, job);
+    }
+}
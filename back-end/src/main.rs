@@ -3,20 +3,47 @@
     windows_subsystem = "windows"
 )]
 
+mod arc_config;
+mod bundle;
+mod bundled_mods;
 mod config;
+mod hardware;
+mod jobs;
+mod merge;
+mod mods;
 mod optimizer;
+mod packs;
+mod plugins;
+mod presets;
 mod profile;
+mod quarantine;
+mod saves;
+mod udev;
 mod utils;
+mod verify;
+mod web_service;
 
+use arc_config::ArcConfig;
+use bundled_mods::BundledMod;
 use config::{AdvancedOption, LocalPersistantData, Optimization, OptimizerConfig};
+use jobs::{JobQueue, JobStatus};
+use merge::MergeConflict;
+use mods::{ModConflict, ModImportReport, ModInfo};
+use packs::{DataPack, PackSecuritySettings};
+use plugins::PluginInfo;
+use presets::SettingsPreset;
 use profile::UserProfile;
+use quarantine::{QuarantineEntry, QuarantineRetention};
+use saves::SaveSnapshot;
+use std::path::{Path, PathBuf};
 use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use sysinfo::System;
 use tauri::{AppHandle, Manager};
 use tauri_plugin_dialog::DialogExt;
 use tauri_plugin_log::{Target, TargetKind, WEBVIEW_TARGET};
-
-use crate::config::UserStatus;
+use udev::UdevRuleStatus;
+use verify::VerifiedUserStatus;
+use web_service::WebServiceSettings;
 
 #[cfg(target_os = "windows")]
 pub static BUNDLED_WEBVIEW2_INSTALLER_DATA: &[u8] =
@@ -25,6 +52,7 @@ pub static BUNDLED_WEBVIEW2_INSTALLER_DATA: &[u8] =
 struct AppState {
     app_handle: AppHandle,
     config: RwLock<OptimizerConfig>,
+    jobs: JobQueue,
 }
 
 impl AppState {
@@ -129,7 +157,7 @@ impl AppState {
         });
     }
 
-    pub fn read_config(&self) -> RwLockReadGuard<OptimizerConfig> {
+    pub fn read_config(&self) -> RwLockReadGuard<'_, OptimizerConfig> {
         let config = self
             .config
             .read()
@@ -137,7 +165,7 @@ impl AppState {
         config
     }
 
-    pub fn write_config(&self) -> RwLockWriteGuard<OptimizerConfig> {
+    pub fn write_config(&self) -> RwLockWriteGuard<'_, OptimizerConfig> {
         let config = self
             .config
             .write()
@@ -179,6 +207,7 @@ fn main() {
             app.manage(AppState {
                 app_handle: app.app_handle().clone(),
                 config: RwLock::new(loaded_config),
+                jobs: JobQueue::start(app.app_handle().clone()),
             });
             let state: tauri::State<AppState> = app.state();
             state.refresh_title();
@@ -197,66 +226,102 @@ fn main() {
         .invoke_handler(tauri::generate_handler![
             select_emu_data_folder,
             update_selected_user,
-            apply_optimization,
+            queue_optimization,
+            optimize_selected,
+            optimize_profiles,
+            get_job_status,
+            list_jobs,
+            cancel_job,
             get_user_status,
             query_local_persistant_data,
             query_config,
+            get_settings_presets,
+            accept_settings_conflicts,
+            get_input_profiles,
+            get_bundled_mods,
+            update_web_service_settings,
+            restore_web_service,
+            list_mods,
+            set_mod_enabled,
+            find_mod_conflicts,
+            resolve_mod_conflict,
+            import_mod_archive,
+            list_skyline_plugins,
+            set_skyline_plugin_enabled,
+            remove_skyline_plugin,
+            export_save,
+            import_save,
+            list_save_snapshots,
+            delete_save_snapshot,
+            list_data_packs,
+            import_data_pack,
+            remove_data_pack,
+            update_pack_security_settings,
+            get_arc_config,
+            set_arc_config,
+            list_quarantine,
+            restore_quarantine,
+            update_quarantine_retention,
+            get_gc_adapter_rule_status,
+            install_gc_adapter_rule,
         ])
         .run(tauri::generate_context!())
         .expect("Error while running tauri application");
 }
 
 #[tauri::command]
-fn apply_optimization(
-    app_handle: tauri::AppHandle,
+fn queue_optimization(
+    state: tauri::State<AppState>,
     user_profile: UserProfile,
     optimization: Optimization,
     advanced_options: Vec<AdvancedOption>,
-) -> Result<(), String> {
-    let state: tauri::State<AppState> = app_handle.state();
-    let mut config = state.write_config();
-    log::info!(
-        "Applying Optimization for user {}: {}",
-        user_profile.name,
-        optimization
-    );
-    let optimization_result = match optimization {
-        Optimization::Settings => optimizer::optimize_settings(&config, &user_profile),
-        Optimization::Mods => optimizer::optimize_mods(&config, &user_profile, advanced_options),
-        Optimization::Save => optimizer::optimize_save(&config, &user_profile),
-    };
-    if optimization_result.is_err() {
-        log::error!("Error applying optimization");
-        return Err(optimization_result.unwrap_err().to_string());
-    }
+) -> Result<u64, String> {
+    state
+        .jobs
+        .queue(vec![user_profile], vec![(optimization, advanced_options)])
+        .map_err(|e| e.to_string())
+}
 
-    let local_data = &mut config.local_data;
-    match (
-        optimization,
-        local_data.user_statuses.get_mut(&user_profile),
-    ) {
-        (Optimization::Settings, Some(u)) => {
-            u.settings_optimized = true;
-        }
-        (Optimization::Mods, Some(u)) => {
-            u.mods_optimized = true;
-        }
-        (Optimization::Save, Some(u)) => {
-            u.save_optimized = true;
-        }
-        (o, None) => {
-            local_data.user_statuses.insert(
-                user_profile,
-                UserStatus {
-                    settings_optimized: o == Optimization::Settings,
-                    mods_optimized: o == Optimization::Mods,
-                    save_optimized: o == Optimization::Save,
-                },
-            );
-        }
-    }
-    config.local_data.save(app_handle.path());
-    Ok(())
+// queues every selected optimization as a single job with one status update at the end
+#[tauri::command]
+fn optimize_selected(
+    state: tauri::State<AppState>,
+    user_profile: UserProfile,
+    optimizations: Vec<(Optimization, Vec<AdvancedOption>)>,
+) -> Result<u64, String> {
+    state
+        .jobs
+        .queue(vec![user_profile], optimizations)
+        .map_err(|e| e.to_string())
+}
+
+// settings and skyline are applied once, saves and arcropolis configs for every user
+#[tauri::command]
+fn optimize_profiles(
+    state: tauri::State<AppState>,
+    user_profiles: Option<Vec<UserProfile>>,
+    optimizations: Vec<(Optimization, Vec<AdvancedOption>)>,
+) -> Result<u64, String> {
+    let user_profiles = user_profiles.unwrap_or_else(|| state.read_config().user_profiles.clone());
+    state
+        .jobs
+        .queue(user_profiles, optimizations)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_job_status(state: tauri::State<AppState>, job_id: u64) -> Result<JobStatus, String> {
+    state.jobs.get_status(job_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_jobs(state: tauri::State<AppState>) -> Vec<JobStatus> {
+    state.jobs.list()
+}
+
+#[tauri::command]
+fn cancel_job(state: tauri::State<AppState>, job_id: u64) -> Result<(), String> {
+    state.jobs.cancel(job_id).map_err(|e| e.to_string())
 }
 
 // should be called by the front-end only once, and then cached to avoid cloning too much
@@ -312,15 +377,220 @@ fn update_selected_user(state: tauri::State<AppState>, user_profile: Option<User
     state.write_config().local_data.selected_user_profile = user_profile;
 }
 
+// hashes the installed files, so it runs off the main thread and on a snapshot of the config
+#[tauri::command(async)]
+fn get_user_status(state: tauri::State<AppState>, user_profile: UserProfile) -> VerifiedUserStatus {
+    let config = state.read_config().clone();
+    verify::verify_user_status(&config, &user_profile)
+}
+
+#[tauri::command]
+fn get_input_profiles(state: tauri::State<AppState>) -> Result<Vec<String>, String> {
+    let config = state.read_config().clone();
+    optimizer::input_profile_names(&packs::ContentSources::load(&config)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_settings_presets() -> Vec<SettingsPreset> {
+    presets::SETTINGS_PRESETS.to_vec()
+}
+
+// replaces the user's values with the bundled ones for conflicts reported by a settings merge
+#[tauri::command]
+fn accept_settings_conflicts(
+    state: tauri::State<AppState>,
+    file: String,
+    conflicts: Vec<MergeConflict>,
+) -> Result<(), String> {
+    optimizer::accept_settings_conflicts(&state.read_config(), &file, &conflicts)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_bundled_mods() -> Vec<BundledMod> {
+    bundled_mods::BUNDLED_MODS.to_vec()
+}
+
+#[tauri::command]
+fn update_web_service_settings(app_handle: tauri::AppHandle, web_service: WebServiceSettings) {
+    let state: tauri::State<AppState> = app_handle.state();
+    let mut config = state.write_config();
+    config.local_data.web_service = web_service;
+    config.local_data.save(app_handle.path());
+}
+
+#[tauri::command]
+fn update_pack_security_settings(
+    app_handle: tauri::AppHandle,
+    pack_security: PackSecuritySettings,
+) {
+    let state: tauri::State<AppState> = app_handle.state();
+    let mut config = state.write_config();
+    config.local_data.pack_security = pack_security;
+    config.local_data.save(app_handle.path());
+}
+
+#[tauri::command]
+fn restore_web_service(state: tauri::State<AppState>) -> Result<(), String> {
+    web_service::restore_web_service(&state.read_config()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_mods(state: tauri::State<AppState>) -> Result<Vec<ModInfo>, String> {
+    mods::list_mods(&state.read_config()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_mod_enabled(
+    state: tauri::State<AppState>,
+    name: String,
+    enabled: bool,
+) -> Result<(), String> {
+    mods::set_mod_enabled(&state.read_config(), &name, enabled).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn find_mod_conflicts(state: tauri::State<AppState>) -> Result<Vec<ModConflict>, String> {
+    mods::find_conflicts(&state.read_config()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn resolve_mod_conflict(
+    state: tauri::State<AppState>,
+    winner: String,
+    loser: String,
+) -> Result<(), String> {
+    mods::resolve_conflict(&state.read_config(), &winner, &loser).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn import_mod_archive(
+    state: tauri::State<AppState>,
+    archive_path: PathBuf,
+    name: Option<String>,
+) -> Result<ModImportReport, String> {
+    mods::import_mod_archive(&state.read_config(), &archive_path, name.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_skyline_plugins(state: tauri::State<AppState>) -> Result<Vec<PluginInfo>, String> {
+    plugins::list_plugins(&state.read_config()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_skyline_plugin_enabled(
+    state: tauri::State<AppState>,
+    file_name: String,
+    enabled: bool,
+) -> Result<(), String> {
+    plugins::set_plugin_enabled(&state.read_config(), &file_name, enabled)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn remove_skyline_plugin(state: tauri::State<AppState>, file_name: String) -> Result<(), String> {
+    plugins::remove_plugin(&state.read_config(), &file_name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn export_save(
+    state: tauri::State<AppState>,
+    user_profile: UserProfile,
+    label: Option<String>,
+    destination: Option<PathBuf>,
+) -> Result<SaveSnapshot, String> {
+    saves::export_save(&state.read_config(), &user_profile, label, destination)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn import_save(
+    state: tauri::State<AppState>,
+    user_profile: UserProfile,
+    archive_path: PathBuf,
+) -> Result<(), String> {
+    saves::import_save(&state.read_config(), &user_profile, &archive_path)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_save_snapshots(state: tauri::State<AppState>) -> Result<Vec<SaveSnapshot>, String> {
+    saves::list_snapshots(&state.read_config()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_save_snapshot(state: tauri::State<AppState>, file_name: String) -> Result<(), String> {
+    saves::delete_snapshot(&state.read_config(), &file_name).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
-fn get_user_status(state: tauri::State<AppState>, user_profile: UserProfile) -> UserStatus {
-    if let Some(status) = state
-        .read_config()
-        .local_data
-        .user_statuses
-        .get(&user_profile)
-    {
-        return status.clone();
+fn list_data_packs(state: tauri::State<AppState>) -> Result<Vec<DataPack>, String> {
+    packs::list_packs(&state.read_config()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn import_data_pack(
+    state: tauri::State<AppState>,
+    archive_path: PathBuf,
+) -> Result<DataPack, String> {
+    packs::import_pack(&state.read_config(), &archive_path).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn remove_data_pack(state: tauri::State<AppState>, file_name: String) -> Result<(), String> {
+    packs::remove_pack(&state.read_config(), &file_name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_arc_config(
+    state: tauri::State<AppState>,
+    user_profile: UserProfile,
+) -> Result<ArcConfig, String> {
+    arc_config::get_arc_config(&state.read_config(), &user_profile).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_arc_config(
+    state: tauri::State<AppState>,
+    user_profile: UserProfile,
+    arc_config: ArcConfig,
+) -> Result<(), String> {
+    arc_config::set_arc_config(&state.read_config(), &user_profile, &arc_config)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_quarantine(state: tauri::State<AppState>) -> Result<Vec<QuarantineEntry>, String> {
+    quarantine::list_quarantine(&state.read_config()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn restore_quarantine(state: tauri::State<AppState>, id: String) -> Result<(), String> {
+    quarantine::restore_quarantine(&state.read_config(), &id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn update_quarantine_retention(app_handle: tauri::AppHandle, retention: QuarantineRetention) {
+    let state: tauri::State<AppState> = app_handle.state();
+    let mut config = state.write_config();
+    config.local_data.quarantine_retention = retention;
+    config.local_data.save(app_handle.path());
+}
+
+// the adapter only needs a udev rule on linux
+#[tauri::command]
+fn get_gc_adapter_rule_status() -> Result<UdevRuleStatus, String> {
+    if !cfg!(target_os = "linux") {
+        return Err("udev rules are only used on Linux".to_string());
+    }
+    udev::gc_adapter_rule_status(Path::new("/")).map_err(|e| e.to_string())
+}
+
+#[tauri::command(async)]
+fn install_gc_adapter_rule() -> Result<PathBuf, String> {
+    if !cfg!(target_os = "linux") {
+        return Err("udev rules are only used on Linux".to_string());
     }
-    UserStatus::default()
+    udev::install_gc_adapter_rule(Path::new("/")).map_err(|e| e.to_string())
 }
@@ -0,0 +1,342 @@
+use crate::utils::{io_error, write_atomic};
+use ini::Ini;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub type IniKey = (Option<String>, String);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeConflict {
+    pub section: Option<String>,
+    pub key: String,
+    pub base: Option<String>,
+    pub user: Option<String>,
+    pub bundled: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct MergeReport {
+    pub file: String,
+    pub applied: usize,
+    pub kept: usize,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+pub fn ini_keys(ini: &Ini) -> Vec<IniKey> {
+    ini.iter()
+        .flat_map(|(section, properties)| {
+            properties
+                .iter()
+                .map(move |(key, _)| (section.map(String::from), key.to_string()))
+        })
+        .collect()
+}
+
+pub fn ini_value(ini: &Ini, (section, key): &IniKey) -> Option<String> {
+    ini.get_from(section.as_deref(), key).map(String::from)
+}
+
+fn set_ini_value(ini: &mut Ini, (section, key): &IniKey, value: Option<&String>) {
+    match value {
+        Some(v) => {
+            ini.with_section(section.as_deref()).set(key, v);
+        }
+        None => {
+            ini.delete_from(section.as_deref(), key);
+        }
+    }
+}
+
+// Without a base only missing values are filled in, values the user set are reported as conflicts
+pub fn merge_ini(base: Option<&Ini>, current: &mut Ini, bundled: &Ini) -> MergeReport {
+    let mut report = MergeReport::default();
+    let mut keys = ini_keys(bundled);
+    for key in base.map(ini_keys).unwrap_or_default() {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    for key in keys.iter() {
+        let user = ini_value(current, key);
+        let new = ini_value(bundled, key);
+        if user == new {
+            continue;
+        }
+        let base = base.and_then(|b| ini_value(b, key));
+        if user == base {
+            set_ini_value(current, key, new.as_ref());
+            report.applied += 1;
+        } else if new == base {
+            report.kept += 1;
+        } else {
+            report.conflicts.push(MergeConflict {
+                section: key.0.clone(),
+                key: key.1.clone(),
+                base,
+                user,
+                bundled: new,
+            });
+        }
+    }
+    report
+}
+
+fn load_ini(path: &Path) -> io::Result<Option<Ini>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    Ini::load_from_file_noescape(path)
+        .map(Some)
+        .ok()
+        .ok_or(io_error!(
+            InvalidData,
+            "Unable to parse settings file: {}",
+            path.to_string_lossy()
+        ))
+}
+
+fn save_ini(path: &Path, ini: &Ini) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut contents = vec![];
+    ini.write_to_policy(&mut contents, ini::EscapePolicy::Nothing)
+        .ok()
+        .ok_or(io_error!(
+            NotFound,
+            "Unable to save settings file: {}",
+            path.to_string_lossy()
+        ))?;
+    write_atomic(path, &contents)
+}
+
+// older versions overwrote the target with the file they bundled and saved no base,
+// so that file is the base of any existing target until one is saved
+pub fn merge_ini_file(
+    bundled: &Ini,
+    previous_bundled: Option<&Ini>,
+    target: &Path,
+    base_path: &Path,
+) -> io::Result<MergeReport> {
+    let current = load_ini(target)?;
+    let base = match (load_ini(base_path)?, &current) {
+        (Some(base), _) => Some(base),
+        (None, Some(_)) => previous_bundled.cloned(),
+        (None, None) => None,
+    };
+    let mut current = current.unwrap_or_default();
+
+    let mut report = merge_ini(base.as_ref(), &mut current, bundled);
+    report.file = target
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    log::info!(
+        "Merged {}: {} applied, {} kept, {} conflicts",
+        report.file,
+        report.applied,
+        report.kept,
+        report.conflicts.len()
+    );
+    for conflict in report.conflicts.iter() {
+        log::warn!("Settings conflict in {}: {:?}", report.file, conflict);
+    }
+
+    // conflicting keys keep their old base so they are reported again until resolved
+    let mut new_base = bundled.clone();
+    for conflict in report.conflicts.iter() {
+        let key = (conflict.section.clone(), conflict.key.clone());
+        set_ini_value(&mut new_base, &key, conflict.base.as_ref());
+    }
+
+    save_ini(target, &current)?;
+    save_ini(base_path, &new_base)?;
+    Ok(report)
+}
+
+// the bundled value replaces the user's one and becomes the base, resolving the conflict
+pub fn accept_bundled_values(
+    conflicts: &[MergeConflict],
+    target: &Path,
+    base_path: &Path,
+) -> io::Result<()> {
+    let mut current = load_ini(target)?.unwrap_or_default();
+    let mut base = load_ini(base_path)?.unwrap_or_default();
+    for conflict in conflicts {
+        log::info!(
+            "Using bundled value for {}: {:?}",
+            target.to_string_lossy(),
+            conflict
+        );
+        let key = (conflict.section.clone(), conflict.key.clone());
+        set_ini_value(&mut current, &key, conflict.bundled.as_ref());
+        set_ini_value(&mut base, &key, conflict.bundled.as_ref());
+    }
+    save_ini(target, &current)?;
+    save_ini(base_path, &base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn ini(contents: &str) -> Ini {
+        Ini::load_from_str_noescape(contents).unwrap()
+    }
+
+    fn value(ini: &Ini, key: &str) -> Option<String> {
+        ini_value(ini, &(Some(String::from("Renderer")), String::from(key)))
+    }
+
+    #[test]
+    fn user_change_is_kept() {
+        let base = ini("[Renderer]\nres=1\n");
+        let mut current = ini("[Renderer]\nres=2\n");
+        let report = merge_ini(Some(&base), &mut current, &ini("[Renderer]\nres=1\n"));
+        assert_eq!(value(&current, "res").as_deref(), Some("2"));
+        assert_eq!((report.applied, report.kept), (0, 1));
+        assert!(report.conflicts.is_empty());
+    }
+
+    #[test]
+    fn bundle_change_is_applied() {
+        let base = ini("[Renderer]\nres=1\n");
+        let mut current = ini("[Renderer]\nres=1\n");
+        let report = merge_ini(Some(&base), &mut current, &ini("[Renderer]\nres=3\n"));
+        assert_eq!(value(&current, "res").as_deref(), Some("3"));
+        assert_eq!((report.applied, report.kept), (1, 0));
+        assert!(report.conflicts.is_empty());
+    }
+
+    #[test]
+    fn both_changed_is_a_conflict() {
+        let base = ini("[Renderer]\nres=1\n");
+        let mut current = ini("[Renderer]\nres=2\n");
+        let report = merge_ini(Some(&base), &mut current, &ini("[Renderer]\nres=3\n"));
+        assert_eq!(value(&current, "res").as_deref(), Some("2"));
+        assert_eq!(report.applied, 0);
+        let conflict = &report.conflicts[0];
+        assert_eq!(conflict.section.as_deref(), Some("Renderer"));
+        assert_eq!(conflict.key, "res");
+        assert_eq!(conflict.base.as_deref(), Some("1"));
+        assert_eq!(conflict.user.as_deref(), Some("2"));
+        assert_eq!(conflict.bundled.as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn no_base_fills_missing_values_and_reports_user_values() {
+        let mut current = ini("[Renderer]\nres=2\nvsync=0\n");
+        let report = merge_ini(
+            None,
+            &mut current,
+            &ini("[Renderer]\nres=3\nvsync=0\nshaders=1\n"),
+        );
+        assert_eq!(value(&current, "res").as_deref(), Some("2"));
+        assert_eq!(value(&current, "shaders").as_deref(), Some("1"));
+        assert_eq!(report.applied, 1);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].key, "res");
+        assert_eq!(report.conflicts[0].base, None);
+    }
+
+    #[test]
+    fn key_removed_from_bundle_is_removed_unless_changed() {
+        let base = ini("[Renderer]\nres=1\nold=1\nedited=1\n");
+        let mut current = ini("[Renderer]\nres=1\nold=1\nedited=2\n");
+        let report = merge_ini(Some(&base), &mut current, &ini("[Renderer]\nres=1\n"));
+        assert_eq!(value(&current, "old"), None);
+        assert_eq!(value(&current, "edited").as_deref(), Some("2"));
+        assert_eq!(report.applied, 1);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].key, "edited");
+        assert_eq!(report.conflicts[0].bundled, None);
+    }
+
+    // a settings file and its base in a fresh folder under the system temp folder
+    fn settings_files(name: &str, target: Option<&str>, base: Option<&str>) -> (PathBuf, PathBuf) {
+        let folder =
+            std::env::temp_dir().join(format!("merge-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(folder.as_path());
+        fs::create_dir_all(folder.as_path()).unwrap();
+        let paths = (folder.join("custom.ini"), folder.join("base.ini"));
+        for (path, contents) in [(&paths.0, target), (&paths.1, base)] {
+            if let Some(contents) = contents {
+                fs::write(path, contents).unwrap();
+            }
+        }
+        paths
+    }
+
+    #[test]
+    fn conflicts_keep_their_old_base() {
+        let (target, base_path) = settings_files(
+            "old_base",
+            Some("[Renderer]\nres=2\n"),
+            Some("[Renderer]\nres=1\n"),
+        );
+        let bundled = ini("[Renderer]\nres=3\n");
+
+        let report = merge_ini_file(&bundled, None, &target, &base_path).unwrap();
+        assert_eq!(report.conflicts.len(), 1);
+        let saved_base = Ini::load_from_file_noescape(base_path.as_path()).unwrap();
+        assert_eq!(value(&saved_base, "res").as_deref(), Some("1"));
+        // still reported the next time the same bundle is merged
+        let report = merge_ini_file(&bundled, None, &target, &base_path).unwrap();
+        assert_eq!(report.conflicts.len(), 1);
+        fs::remove_dir_all(target.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn previous_bundle_is_the_base_of_an_existing_file() {
+        let (target, base_path) = settings_files(
+            "previous_bundle",
+            Some("[Renderer]\nres=1\nvsync=0\n"),
+            None,
+        );
+        let previous = ini("[Renderer]\nres=1\nvsync=1\n");
+        let bundled = ini("[Renderer]\nres=3\nvsync=1\n");
+
+        let report = merge_ini_file(&bundled, Some(&previous), &target, &base_path).unwrap();
+        let current = Ini::load_from_file_noescape(target.as_path()).unwrap();
+        assert_eq!(value(&current, "res").as_deref(), Some("3"));
+        assert_eq!(value(&current, "vsync").as_deref(), Some("0"));
+        assert_eq!((report.applied, report.kept), (1, 1));
+        assert!(report.conflicts.is_empty());
+        fs::remove_dir_all(target.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn previous_bundle_is_not_the_base_of_a_new_file() {
+        let (target, base_path) = settings_files("new_file", None, None);
+        let previous = ini("[Renderer]\nres=3\n");
+        let bundled = ini("[Renderer]\nres=3\n");
+
+        let report = merge_ini_file(&bundled, Some(&previous), &target, &base_path).unwrap();
+        let current = Ini::load_from_file_noescape(target.as_path()).unwrap();
+        assert_eq!(value(&current, "res").as_deref(), Some("3"));
+        assert_eq!(report.applied, 1);
+        fs::remove_dir_all(target.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn accepted_conflicts_are_resolved() {
+        let (target, base_path) = settings_files(
+            "accepted",
+            Some("[Renderer]\nres=2\n"),
+            Some("[Renderer]\nres=1\n"),
+        );
+        let bundled = ini("[Renderer]\nres=3\n");
+
+        let report = merge_ini_file(&bundled, None, &target, &base_path).unwrap();
+        accept_bundled_values(&report.conflicts, &target, &base_path).unwrap();
+        let current = Ini::load_from_file_noescape(target.as_path()).unwrap();
+        assert_eq!(value(&current, "res").as_deref(), Some("3"));
+        let report = merge_ini_file(&bundled, None, &target, &base_path).unwrap();
+        assert!(report.conflicts.is_empty());
+        fs::remove_dir_all(target.parent().unwrap()).unwrap();
+    }
+}
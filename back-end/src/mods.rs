@@ -0,0 +1,345 @@
+use crate::config::OptimizerConfig;
+use crate::plugins;
+use crate::utils::{dir_stats, io_error, DirStats};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use zip::ZipArchive;
+
+// ARCropolis skips any mod folder whose name starts with a dot
+const DISABLED_MOD_PREFIX: &str = ".";
+// top-level folders of the game filesystem, used to find the real root of a mod archive
+const MOD_ROOT_FOLDERS: &[&str] = &["fighter", "stage", "ui", "sound", "effect", "camera"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModInfo {
+    pub name: String,
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub stats: DirStats,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModConflict {
+    pub mods: (String, String),
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModImportReport {
+    pub name: String,
+    pub file_count: usize,
+    pub plugins: Vec<String>,
+}
+
+fn validate_mod_name(name: &str) -> io::Result<()> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) if !name.starts_with(DISABLED_MOD_PREFIX) => Ok(()),
+        _ => Err(io_error!(InvalidInput, "Invalid mod name: {}", name)),
+    }
+}
+
+fn get_mod_path(mods_path: &Path, name: &str, enabled: bool) -> PathBuf {
+    match enabled {
+        true => mods_path.join(name),
+        false => mods_path.join(format!("{}{}", DISABLED_MOD_PREFIX, name)),
+    }
+}
+
+pub fn list_mods(config: &OptimizerConfig) -> io::Result<Vec<ModInfo>> {
+    let mods_path = config.get_arc_mods_folder()?;
+    if !mods_path.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut mods = vec![];
+    for entry in fs::read_dir(mods_path)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let folder_name = entry.file_name().to_string_lossy().to_string();
+        let (name, enabled) = match folder_name.strip_prefix(DISABLED_MOD_PREFIX) {
+            Some(name) => (name.to_string(), false),
+            None => (folder_name, true),
+        };
+        mods.push(ModInfo {
+            name,
+            enabled,
+            stats: dir_stats(entry.path().as_path())?,
+        });
+    }
+    mods.sort_by_key(|m| m.name.to_lowercase());
+    Ok(mods)
+}
+
+pub fn set_mod_enabled(config: &OptimizerConfig, name: &str, enabled: bool) -> io::Result<()> {
+    validate_mod_name(name)?;
+    let mods_path = config.get_arc_mods_folder()?;
+    let from = get_mod_path(&mods_path, name, !enabled);
+    let to = get_mod_path(&mods_path, name, enabled);
+    if to.is_dir() {
+        log::info!("Mod {} already has enabled state: {}", name, enabled);
+        return Ok(());
+    }
+    if !from.is_dir() {
+        return Err(io_error!(NotFound, "Unable to find mod: {}", name));
+    }
+    log::info!(
+        "{} mod: {}",
+        if enabled { "Enabling" } else { "Disabling" },
+        name
+    );
+    fs::rename(from, to)
+}
+
+// game files relative to the mod root, files at the root itself are mod metadata (info.toml, preview.webp...)
+fn index_mod_files(
+    mod_path: &Path,
+    relative_path: &Path,
+    files: &mut Vec<String>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(mod_path.join(relative_path))? {
+        let entry = entry?;
+        let entry_path = relative_path.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            index_mod_files(mod_path, entry_path.as_path(), files)?;
+        } else if relative_path.components().next().is_some() {
+            let file = entry_path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push(file);
+        }
+    }
+    Ok(())
+}
+
+pub fn find_conflicts(config: &OptimizerConfig) -> io::Result<Vec<ModConflict>> {
+    let mods_path = config.get_arc_mods_folder()?;
+    let mut file_owners: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for mod_info in list_mods(config)?.into_iter().filter(|m| m.enabled) {
+        let mut files = vec![];
+        index_mod_files(&mods_path.join(&mod_info.name), Path::new(""), &mut files)?;
+        for file in files {
+            file_owners
+                .entry(file.to_lowercase())
+                .or_default()
+                .push(mod_info.name.clone());
+        }
+    }
+
+    let mut conflicts: BTreeMap<(String, String), Vec<String>> = BTreeMap::new();
+    for (file, owners) in file_owners.into_iter().filter(|(_, o)| o.len() > 1) {
+        for (i, first) in owners.iter().enumerate() {
+            for second in owners.iter().skip(i + 1) {
+                conflicts
+                    .entry((first.clone(), second.clone()))
+                    .or_default()
+                    .push(file.clone());
+            }
+        }
+    }
+    log::info!("Found {} conflicting mod pairs", conflicts.len());
+    Ok(conflicts
+        .into_iter()
+        .map(|(mods, files)| ModConflict { mods, files })
+        .collect())
+}
+
+pub fn resolve_conflict(config: &OptimizerConfig, winner: &str, loser: &str) -> io::Result<()> {
+    let is_conflict = find_conflicts(config)?.iter().any(|c| {
+        (c.mods.0 == winner && c.mods.1 == loser) || (c.mods.0 == loser && c.mods.1 == winner)
+    });
+    if !is_conflict {
+        return Err(io_error!(
+            NotFound,
+            "No conflict found between mods {} and {}",
+            winner,
+            loser
+        ));
+    }
+    log::info!("Resolving conflict in favor of {} over {}", winner, loser);
+    set_mod_enabled(config, loser, false)
+}
+
+fn find_archive_mod_root(entries: &[PathBuf]) -> Option<Vec<String>> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let components: Vec<String> = entry
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect();
+            let folders = &components[..components.len().saturating_sub(1)];
+            folders
+                .iter()
+                .position(|f| MOD_ROOT_FOLDERS.contains(&f.to_lowercase().as_str()))
+                .map(|i| folders[..i].to_vec())
+        })
+        .min_by_key(|root| root.len())
+}
+
+fn extract_mod_files(
+    archive: &mut ZipArchive<fs::File>,
+    entries: &[(usize, PathBuf)],
+    mod_root: &Path,
+    target: &Path,
+) -> io::Result<usize> {
+    let mut file_count = 0;
+    for (i, path) in entries.iter() {
+        let relative_path = match path.strip_prefix(mod_root) {
+            Ok(r) => r,
+            Err(_) => {
+                log::info!(
+                    "Skipping file outside of mod root: {}",
+                    path.to_string_lossy()
+                );
+                continue;
+            }
+        };
+        let file_path = target.join(relative_path);
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = archive
+            .by_index(*i)
+            .map_err(|e| io_error!(InvalidData, "Unable to read mod archive: {}", e))?;
+        io::copy(&mut file, &mut fs::File::create(file_path)?)?;
+        file_count += 1;
+    }
+    Ok(file_count)
+}
+
+pub fn import_mod_archive(
+    config: &OptimizerConfig,
+    archive_path: &Path,
+    name: Option<&str>,
+) -> io::Result<ModImportReport> {
+    log::info!("Importing mod archive: {}", archive_path.to_string_lossy());
+    let mut archive = ZipArchive::new(fs::File::open(archive_path)?)
+        .map_err(|e| io_error!(InvalidData, "Unable to read mod archive: {}", e))?;
+    let mut entries = vec![];
+    for i in 0..archive.len() {
+        let file = archive
+            .by_index(i)
+            .map_err(|e| io_error!(InvalidData, "Unable to read mod archive: {}", e))?;
+        match file.enclosed_name() {
+            Some(path) if !file.is_dir() => entries.push((i, path.to_path_buf())),
+            Some(_) => {}
+            None => log::warn!("Skipping unsafe archive entry: {}", file.name()),
+        }
+    }
+
+    let (plugin_entries, mod_entries): (Vec<_>, Vec<_>) =
+        entries.into_iter().partition(|(_, p)| {
+            p.extension()
+                .map(|e| e.eq_ignore_ascii_case("nro"))
+                .unwrap_or(false)
+        });
+    let mod_paths: Vec<PathBuf> = mod_entries.iter().map(|(_, p)| p.clone()).collect();
+    let mod_root = find_archive_mod_root(&mod_paths);
+    if mod_root.is_none() && plugin_entries.is_empty() {
+        return Err(io_error!(
+            NotFound,
+            "Unable to find a mod root ({}) in archive",
+            MOD_ROOT_FOLDERS.join(", ")
+        ));
+    }
+
+    // plugins are checked up front so a collision doesn't leave the mod installed without them
+    let plugin_folder = plugins::get_plugin_folder(config, true)?;
+    let disabled_plugin_folder = plugins::get_plugin_folder(config, false)?;
+    let mut plugin_names: Vec<String> = vec![];
+    for (_, path) in plugin_entries.iter() {
+        let file_name = match path.file_name() {
+            Some(f) => f.to_string_lossy().to_string(),
+            None => continue,
+        };
+        if plugin_names.contains(&file_name)
+            || plugin_folder.join(&file_name).exists()
+            || disabled_plugin_folder.join(&file_name).exists()
+        {
+            return Err(io_error!(
+                AlreadyExists,
+                "A skyline plugin named {} is already installed",
+                file_name
+            ));
+        }
+        plugin_names.push(file_name);
+    }
+
+    let mut report = ModImportReport {
+        name: String::new(),
+        file_count: 0,
+        plugins: vec![],
+    };
+    if let Some(mod_root) = mod_root {
+        let archive_stem = archive_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        report.name = name
+            .map(String::from)
+            .or(mod_root
+                .last()
+                .filter(|r| !r.eq_ignore_ascii_case("mods"))
+                .cloned())
+            .unwrap_or(archive_stem);
+        validate_mod_name(&report.name)?;
+        let mods_path = config.get_arc_mods_folder()?;
+        let mod_path = mods_path.join(&report.name);
+        if mod_path.exists() || get_mod_path(&mods_path, &report.name, false).exists() {
+            return Err(io_error!(
+                AlreadyExists,
+                "A mod named {} is already installed",
+                report.name
+            ));
+        }
+
+        // extract as a disabled mod first so a failed import is never loaded by ARCropolis
+        let staging_path = get_mod_path(&mods_path, &format!("{}.import", report.name), false);
+        if staging_path.exists() {
+            fs::remove_dir_all(staging_path.as_path())?;
+        }
+        let mod_root: PathBuf = mod_root.iter().collect();
+        let extracted = extract_mod_files(&mut archive, &mod_entries, &mod_root, &staging_path)
+            .and_then(|file_count| {
+                log::info!("Installing mod {} ({} files)", report.name, file_count);
+                fs::rename(staging_path.as_path(), mod_path)?;
+                Ok(file_count)
+            });
+        report.file_count = match extracted {
+            Ok(file_count) => file_count,
+            Err(e) => {
+                if staging_path.exists() {
+                    let _ = fs::remove_dir_all(staging_path.as_path());
+                }
+                return Err(e);
+            }
+        };
+    }
+
+    for (i, path) in plugin_entries.iter() {
+        let file_name = match path.file_name() {
+            Some(f) => f.to_string_lossy().to_string(),
+            None => continue,
+        };
+        log::info!("Installing skyline plugin: {}", file_name);
+        fs::create_dir_all(plugin_folder.as_path())?;
+        let mut file = archive
+            .by_index(*i)
+            .map_err(|e| io_error!(InvalidData, "Unable to read mod archive: {}", e))?;
+        // never replaces a plugin, even one installed since the check above
+        let mut target = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(plugin_folder.join(&file_name))?;
+        io::copy(&mut file, &mut target)?;
+        report.plugins.push(file_name);
+    }
+    Ok(report)
+}
@@ -1,131 +1,690 @@
-use crate::utils::io_error;
-use include_dir::{include_dir, Dir};
+use crate::packs::{ContentFile, ContentSources, ContentVersion, PackTarget};
+use crate::utils::{
+    dir_stats, io_error, set_per_game_setting, sha256_file, write_atomic, write_atomic_from,
+};
 use ini::Ini;
+use std::collections::BTreeSet;
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use crate::config::{AdvancedOption, OptimizerConfig, SSBU_TITLE_ID};
+use crate::bundle;
+use crate::bundled_mods::{self, BundledMod, BundledTree, BUNDLED_MODS};
+use crate::config::{AdvancedOption, Optimization, OptimizerConfig, SSBU_TITLE_ID};
+use crate::hardware::{HardwareInfo, SettingAdjustment};
+use crate::merge::{self, MergeConflict, MergeReport};
+use crate::presets::{self, SettingsPreset};
 use crate::profile::UserProfile;
+use crate::quarantine;
+use crate::verify::{self, VerificationIssue};
+use crate::web_service;
+use serde::Serialize;
 
-static BUNDLED_ARC_CONFIG: Dir = include_dir!("$CARGO_MANIFEST_DIR/bundled_data/arc_config");
-static BUNDLED_ARC_MODS: Dir = include_dir!("$CARGO_MANIFEST_DIR/bundled_data/arc_mods");
-static BUNDLED_SKYLINE: Dir = include_dir!("$CARGO_MANIFEST_DIR/bundled_data/skyline");
-static BUNDLED_SAVE_DATA: Dir = include_dir!("$CARGO_MANIFEST_DIR/bundled_data/save");
-static BUNDLED_SSBU_SETTINGS: Dir = include_dir!("$CARGO_MANIFEST_DIR/bundled_data/ssbu_settings");
+pub const PROGRESS_EVENT: &str = "optimization-progress";
 
-fn load_bundled_dir(bundled_dir: &Dir, target: PathBuf) -> io::Result<()> {
+#[derive(Debug, Clone, Serialize)]
+pub struct OptimizationProgress {
+    pub optimization: String,
+    pub files_done: usize,
+    pub total_files: usize,
+    pub bytes_written: u64,
+    pub current_path: PathBuf,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ExtractionReport {
+    pub written: usize,
+    pub skipped: usize,
+    pub removed: usize,
+}
+
+pub struct ProgressTracker<'a> {
+    progress: OptimizationProgress,
+    extraction: ExtractionReport,
+    on_progress: &'a dyn Fn(&OptimizationProgress),
+    cancelled: Option<&'a AtomicBool>,
+}
+
+impl<'a> ProgressTracker<'a> {
+    pub fn new(optimization: String, on_progress: &'a dyn Fn(&OptimizationProgress)) -> Self {
+        ProgressTracker {
+            progress: OptimizationProgress {
+                optimization,
+                files_done: 0,
+                total_files: 0,
+                bytes_written: 0,
+                current_path: PathBuf::new(),
+            },
+            extraction: ExtractionReport::default(),
+            on_progress,
+            cancelled: None,
+        }
+    }
+
+    pub fn with_cancellation(mut self, cancelled: &'a AtomicBool) -> Self {
+        self.cancelled = Some(cancelled);
+        self
+    }
+
+    // only checked before a whole tree is written, a cancelled optimization never leaves one half installed
+    pub fn check_cancelled(&self) -> io::Result<()> {
+        match self.cancelled.map(|c| c.load(Ordering::SeqCst)) {
+            Some(true) => Err(io_error!(
+                Interrupted,
+                "Optimization cancelled: {}",
+                self.progress.optimization
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn add_total_files(&mut self, files: usize) {
+        self.progress.total_files += files;
+    }
+
+    fn file_written(&mut self, path: PathBuf, bytes: u64) {
+        self.extraction.written += 1;
+        self.progress.bytes_written += bytes;
+        self.file_done(path);
+    }
+
+    fn file_skipped(&mut self, path: PathBuf) {
+        self.extraction.skipped += 1;
+        self.file_done(path);
+    }
+
+    fn files_removed(&mut self, files: usize) {
+        self.extraction.removed += files;
+    }
+
+    fn file_done(&mut self, path: PathBuf) {
+        self.progress.files_done += 1;
+        self.progress.current_path = path;
+        (self.on_progress)(&self.progress);
+    }
+
+    pub fn extraction(&self) -> ExtractionReport {
+        self.extraction.clone()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingsPlan {
+    pub preset: String,
+    pub hardware: HardwareInfo,
+    pub adjustments: Vec<SettingAdjustment>,
+    pub merges: Vec<MergeReport>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct OptimizationReport {
+    pub settings_plan: Option<SettingsPlan>,
+    pub extraction: ExtractionReport,
+    // only filled in for mods, an empty list means the mod environment checked out
+    pub verification: Vec<VerificationIssue>,
+    pub content_version: Option<ContentVersion>,
+    // the WebService server applied, none when the step is turned off
+    pub web_service: Option<String>,
+}
+
+fn count_bundled_files(files: &[ContentFile], filter: &dyn Fn(&Path) -> bool) -> usize {
+    files.iter().filter(|f| filter(&f.relative_path)).count()
+}
+
+pub fn is_up_to_date(target: &Path, file: &ContentFile) -> io::Result<bool> {
+    match fs::metadata(target) {
+        Ok(metadata) if metadata.is_file() && metadata.len() == file.size => {
+            Ok(sha256_file(target)? == file.sha256()?)
+        }
+        Ok(_) => Ok(false),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+fn load_bundled_dir(
+    files: &[ContentFile],
+    target: PathBuf,
+    progress: &mut ProgressTracker,
+) -> io::Result<()> {
+    load_bundled_dir_filtered(files, target, &|_| true, progress)
+}
+
+// the filter receives paths relative to the root of the bundled tree
+fn load_bundled_dir_filtered(
+    files: &[ContentFile],
+    target: PathBuf,
+    filter: &dyn Fn(&Path) -> bool,
+    progress: &mut ProgressTracker,
+) -> io::Result<()> {
+    progress.check_cancelled()?;
     log::info!("Creating directory path: {}", target.to_string_lossy());
     fs::create_dir_all(&target)?;
-    for entry in bundled_dir.entries() {
-        if let Some(dir) = entry.as_dir() {
-            let relative_path =
-                dir.path()
-                    .strip_prefix(bundled_dir.path())
-                    .ok()
-                    .ok_or(io_error!(
-                        NotFound,
-                        "Unable to load bundled directory: {}",
-                        dir.path().to_string_lossy()
-                    ))?;
-            load_bundled_dir(dir, target.join(relative_path))?;
-        } else if let Some(file) = entry.as_file() {
-            log::info!("Writing file: {}", file.path().to_string_lossy());
-            fs::write(
-                target.join(file.path().file_name().ok_or(io_error!(
-                    NotFound,
-                    "Unable to load bundled file: {}",
-                    file.path().to_string_lossy()
-                ))?),
-                file.contents(),
-            )?;
+    for file in files {
+        if !filter(&file.relative_path) {
+            log::info!("Skipping: {}", file.source());
+            continue;
+        }
+        let file_path = target.join(&file.relative_path);
+        if is_up_to_date(file_path.as_path(), file)? {
+            log::info!("Up to date: {}", file.source());
+            progress.file_skipped(file_path);
+            continue;
+        }
+        log::info!("Writing file: {}", file.source());
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)?;
         }
+        let bytes = write_atomic_from(file_path.as_path(), &mut file.reader()?)?;
+        progress.file_written(file_path, bytes);
     }
     Ok(())
 }
 
-pub fn optimize_settings(config: &OptimizerConfig, user_profile: &UserProfile) -> io::Result<()> {
-    let config_folder = config.emu_filesystem.config_folder.as_ref();
-    let ssbu_settings_path = config_folder
-        .ok_or(io_error!(NotFound, "Emulator config folder not found"))?
-        .join("custom");
-    load_bundled_dir(&BUNDLED_SSBU_SETTINGS, ssbu_settings_path)?;
-    let main_config_settings_path = config_folder
-        .ok_or(io_error!(NotFound, "Emulator config folder not found"))?
-        .join("qt-config.ini");
-    let mut main_config = Ini::load_from_file_noescape(main_config_settings_path.as_path())
+// removes files left behind in a bundled folder by older versions of the bundle
+fn prune_bundled_dir(
+    files: &[ContentFile],
+    relative_path: &Path,
+    target: &Path,
+    progress: &mut ProgressTracker,
+) -> io::Result<()> {
+    if !target.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(target)? {
+        let entry = entry?;
+        let is_dir = entry.file_type()?.is_dir();
+        let entry_relative_path = relative_path.join(entry.file_name());
+        let is_bundled = |f: &ContentFile| match is_dir {
+            true => f.relative_path.starts_with(&entry_relative_path),
+            false => f.relative_path == entry_relative_path,
+        };
+        if is_dir && files.iter().any(is_bundled) {
+            prune_bundled_dir(files, &entry_relative_path, &entry.path(), progress)?;
+        } else if !files.iter().any(is_bundled) {
+            log::info!("Removing stale file: {}", entry.path().to_string_lossy());
+            if is_dir {
+                let file_count = dir_stats(&entry.path())?.file_count;
+                fs::remove_dir_all(entry.path())?;
+                progress.files_removed(file_count as usize);
+            } else {
+                fs::remove_file(entry.path())?;
+                progress.files_removed(1);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn load_bundled_ini(file: &ContentFile) -> io::Result<Ini> {
+    String::from_utf8(file.contents()?)
         .ok()
-        .ok_or(io_error!(NotFound, "Unable to load main config"))?;
-    let section = main_config
-        .section_mut(Some("WebService"))
+        .and_then(|c| Ini::load_from_str_noescape(&c).ok())
         .ok_or(io_error!(
-            NotFound,
-            "Unable to find WebService section in config"
-        ))?;
-    let emu_name = config.get_emulator_name();
-    section.insert("enable_telemetry\\default", "false");
-    section.insert("enable_telemetry", "false");
-    section.insert("web_api_url\\default", "false");
-    section.insert("web_api_url", "api.ynet-fun.xyz");
-    section.insert(format!("{}_username\\default", emu_name), "false");
-    section.insert(format!("{}_username", emu_name), user_profile.name.as_str());
-    section.insert(format!("{}_token\\default", emu_name), "false");
-    section.insert(
-        format!("{}_token", emu_name),
-        "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
-    );
-    main_config
-        .write_to_file_policy(
-            main_config_settings_path.as_path(),
-            ini::EscapePolicy::Nothing,
-        )
-        .ok()
-        .ok_or(io_error!(NotFound, "Unable to save main config settings"))?;
-    Ok(())
+            InvalidData,
+            "Unable to parse bundled settings: {}",
+            file.source()
+        ))
 }
 
-pub fn optimize_mods(
+// the unmodified settings file as bundled with the app, which older versions wrote as is
+fn previous_bundled_ini(file_name: &str) -> Option<Ini> {
+    bundle::files(Path::new(bundle::SSBU_SETTINGS_TREE))
+        .iter()
+        .find(|f| f.file_name() == file_name)
+        .and_then(|f| f.contents().ok())
+        .and_then(|c| String::from_utf8(c).ok())
+        .and_then(|c| Ini::load_from_str_noescape(&c).ok())
+}
+
+pub fn optimize_settings(
     config: &OptimizerConfig,
-    user_profile: &UserProfile,
-    advanced_options: Vec<AdvancedOption>,
+    sources: &ContentSources,
+    preset: &SettingsPreset,
+    progress: &mut ProgressTracker,
+) -> io::Result<SettingsPlan> {
+    let ssbu_settings_path = config.get_custom_settings_folder()?;
+    let settings_base_path = config.get_settings_base_folder();
+    log::info!("Using settings preset: {}", preset.id);
+    let game_settings_name = format!("{}.ini", SSBU_TITLE_ID);
+    let hardware = HardwareInfo::detect();
+    let mut adjustments = vec![];
+    let mut merges = vec![];
+    let settings_files = sources.files(PackTarget::ConfigCustom)?;
+    progress.add_total_files(settings_files.len());
+    progress.check_cancelled()?;
+    for file in settings_files.iter() {
+        let file_name = file.file_name();
+        let mut bundled_settings = load_bundled_ini(file)?;
+        if file_name == game_settings_name {
+            preset.apply(&mut bundled_settings);
+            adjustments = hardware.plan_adjustments(&bundled_settings);
+            for adjustment in adjustments.iter() {
+                log::info!(
+                    "Setting {}/{} = {}: {}",
+                    adjustment.section,
+                    adjustment.key,
+                    adjustment.value,
+                    adjustment.reason
+                );
+                adjustment.apply(&mut bundled_settings);
+            }
+        }
+        let target = ssbu_settings_path.join(&file_name);
+        merges.push(merge::merge_ini_file(
+            &bundled_settings,
+            previous_bundled_ini(&file_name).as_ref(),
+            target.as_path(),
+            settings_base_path.join(&file_name).as_path(),
+        )?);
+        let bytes = fs::metadata(target.as_path())?.len();
+        progress.file_written(target, bytes);
+    }
+    Ok(SettingsPlan {
+        preset: preset.id.to_string(),
+        hardware,
+        adjustments,
+        merges,
+    })
+}
+
+pub fn accept_settings_conflicts(
+    config: &OptimizerConfig,
+    file_name: &str,
+    conflicts: &[MergeConflict],
 ) -> io::Result<()> {
-    let sdmc_path = config
-        .emu_filesystem
-        .sdmc_folder
-        .as_ref()
-        .ok_or(io_error!(NotFound, "Unable to find sdmc directory"))?;
-    let skyline_path = sdmc_path
-        .join("atmosphere")
-        .join("contents")
-        .join(SSBU_TITLE_ID);
+    let mut components = Path::new(file_name).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(std::path::Component::Normal(_)), None)
+    ) {
+        return Err(io_error!(
+            InvalidInput,
+            "Invalid settings file name: {}",
+            file_name
+        ));
+    }
+    merge::accept_bundled_values(
+        conflicts,
+        config
+            .get_custom_settings_folder()?
+            .join(file_name)
+            .as_path(),
+        config.get_settings_base_folder().join(file_name).as_path(),
+    )
+}
 
+// only files still matching the bundle are removed, anything the user replaced or added is left
+fn remove_bundled_mod(
+    bundled_mod: &BundledMod,
+    skyline: (&Path, &[ContentFile]),
+    arc_mods: (&Path, &[ContentFile]),
+) -> io::Result<()> {
+    for (tree, path) in bundled_mod.paths {
+        let (root, files) = match tree {
+            BundledTree::Skyline => skyline,
+            BundledTree::ArcMods => arc_mods,
+        };
+        for file in files.iter().filter(|f| f.relative_path.starts_with(path)) {
+            let target = root.join(&file.relative_path);
+            if is_up_to_date(target.as_path(), file)? {
+                log::info!("Removing bundled mod file: {}", target.to_string_lossy());
+                fs::remove_file(target)?;
+            } else if target.exists() {
+                log::warn!(
+                    "Leaving bundled mod file that was changed: {}",
+                    target.to_string_lossy()
+                );
+            }
+        }
+        remove_empty_dirs(root.join(path).as_path());
+    }
+    Ok(())
+}
+
+fn remove_empty_dirs(path: &Path) {
+    if let Ok(entries) = fs::read_dir(path) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            remove_empty_dirs(entry.path().as_path());
+        }
+        // fails on purpose when anything is left in the folder
+        let _ = fs::remove_dir(path);
+    }
+}
+
+pub fn clean_mods(
+    config: &OptimizerConfig,
+    advanced_options: &[AdvancedOption],
+    progress: &mut ProgressTracker,
+) -> io::Result<()> {
+    progress.check_cancelled()?;
+    let skyline_path = config.get_skyline_folder()?;
     if advanced_options.contains(&AdvancedOption::CleanSkyline) && skyline_path.is_dir() {
-        log::info!("Removing skyline files...");
-        fs::remove_dir_all(skyline_path.as_path())?;
+        log::info!("Quarantining skyline files...");
+        quarantine::quarantine_dir(config, skyline_path.as_path(), "clean_skyline")?;
     }
 
-    let arc_config_path = config.get_arc_config_folder(user_profile)?;
-    let arc_mods_path = sdmc_path.join("ultimate");
+    // the per-user arcropolis config lives inside this folder too
+    let arc_mods_path = config.get_arc_mods_folder()?;
+    let arc_path = arc_mods_path
+        .parent()
+        .ok_or(io_error!(NotFound, "Unable to find arcropolis directory"))?;
 
-    if advanced_options.contains(&AdvancedOption::CleanArc) && arc_mods_path.is_dir() {
-        log::info!("Removing arcropolis files...");
-        fs::remove_dir_all(arc_config_path.as_path())?;
-        fs::remove_dir_all(arc_mods_path.as_path())?;
+    if advanced_options.contains(&AdvancedOption::CleanArc) && arc_path.is_dir() {
+        log::info!("Quarantining arcropolis files...");
+        quarantine::quarantine_dir(config, arc_path, "clean_arc")?;
     }
+    Ok(())
+}
 
-    let arc_mods_path = arc_mods_path.join("mods");
+// shared by every user, the per-user arcropolis config is installed by optimize_arc_config
+pub fn optimize_mods(
+    config: &OptimizerConfig,
+    sources: &ContentSources,
+    selected_ids: &[String],
+    progress: &mut ProgressTracker,
+) -> io::Result<()> {
+    let selected_mods = selected_ids
+        .iter()
+        .map(|id| bundled_mods::get_bundled_mod(id))
+        .collect::<io::Result<Vec<_>>>()?;
+    let skyline_files = sources.files(PackTarget::SdmcSkyline)?;
+    let arc_mods_files = sources.files(PackTarget::ArcMods)?;
+    // a path missing from the content would leave a deselected mod installed, so it fails the step
+    for bundled_mod in BUNDLED_MODS.iter() {
+        for (tree, path) in bundled_mod.paths {
+            let files = match tree {
+                BundledTree::Skyline => &skyline_files,
+                BundledTree::ArcMods => &arc_mods_files,
+            };
+            if !files.iter().any(|f| f.relative_path.starts_with(path)) {
+                return Err(io_error!(
+                    NotFound,
+                    "Bundled mod {} is missing from the {:?} content: {}",
+                    bundled_mod.id,
+                    tree.pack_target(),
+                    path
+                ));
+            }
+        }
+    }
 
-    load_bundled_dir(&BUNDLED_SKYLINE, skyline_path)?;
+    let skyline_path = config.get_skyline_folder()?;
+    let arc_mods_path = config.get_arc_mods_folder()?;
+    progress.check_cancelled()?;
 
-    load_bundled_dir(&BUNDLED_ARC_CONFIG, arc_config_path)?;
+    // decided from what is on disk, the files are shared so any user may have installed them
+    let bundled_mod_path = |tree: &BundledTree, path: &str| match tree {
+        BundledTree::Skyline => skyline_path.join(path),
+        BundledTree::ArcMods => arc_mods_path.join(path),
+    };
+    for bundled_mod in BUNDLED_MODS.iter().filter(|m| {
+        !selected_mods.iter().any(|s| s.id == m.id)
+            && m.paths
+                .iter()
+                .any(|(tree, path)| bundled_mod_path(tree, path).exists())
+    }) {
+        log::info!("Removing deselected bundled mod: {}", bundled_mod.name);
+        remove_bundled_mod(
+            bundled_mod,
+            (&skyline_path, &skyline_files),
+            (&arc_mods_path, &arc_mods_files),
+        )?;
+    }
 
-    load_bundled_dir(&BUNDLED_ARC_MODS, arc_mods_path)?;
+    let is_selected =
+        |tree: BundledTree, path: &Path| bundled_mods::is_path_selected(tree, path, selected_ids);
+
+    let skyline_filter = |p: &Path| is_selected(BundledTree::Skyline, p);
+    let arc_mods_filter = |p: &Path| is_selected(BundledTree::ArcMods, p);
+    progress.add_total_files(
+        count_bundled_files(&skyline_files, &skyline_filter)
+            + count_bundled_files(&arc_mods_files, &arc_mods_filter),
+    );
+
+    load_bundled_dir_filtered(&skyline_files, skyline_path, &skyline_filter, progress)?;
+
+    load_bundled_dir_filtered(
+        &arc_mods_files,
+        arc_mods_path.clone(),
+        &arc_mods_filter,
+        progress,
+    )?;
+
+    // only bundled mod folders are pruned, the user's own mods are never touched
+    let bundled_mod_folders: BTreeSet<&Path> = arc_mods_files
+        .iter()
+        .filter_map(|f| f.relative_path.iter().next())
+        .map(Path::new)
+        .filter(|f| arc_mods_filter(f))
+        .collect();
+    for folder in bundled_mod_folders {
+        prune_bundled_dir(
+            &arc_mods_files,
+            folder,
+            &arc_mods_path.join(folder),
+            progress,
+        )?;
+    }
 
     Ok(())
 }
 
-pub fn optimize_save(config: &OptimizerConfig, user_profile: &UserProfile) -> io::Result<()> {
+pub fn optimize_arc_config(
+    config: &OptimizerConfig,
+    sources: &ContentSources,
+    user_profile: &UserProfile,
+    progress: &mut ProgressTracker,
+) -> io::Result<()> {
+    let arc_config_path = config.get_arc_config_folder(user_profile)?;
+    let arc_config_files = sources.files(PackTarget::ArcConfig)?;
+    progress.add_total_files(count_bundled_files(&arc_config_files, &|_| true));
+    load_bundled_dir(&arc_config_files, arc_config_path, progress)
+}
+
+pub fn optimize_save(
+    config: &OptimizerConfig,
+    sources: &ContentSources,
+    user_profile: &UserProfile,
+    progress: &mut ProgressTracker,
+) -> io::Result<()> {
     let save_file_path = config.get_save_folder(user_profile)?;
-    load_bundled_dir(&BUNDLED_SAVE_DATA, save_file_path)?;
+    let save_files = sources.files(PackTarget::Save)?;
+    progress.add_total_files(count_bundled_files(&save_files, &|_| true));
+    load_bundled_dir(&save_files, save_file_path, progress)?;
     Ok(())
 }
+
+// profile names are the file names without the extension, as listed by the emulator
+pub fn input_profile_names(sources: &ContentSources) -> io::Result<Vec<String>> {
+    Ok(sources
+        .files(PackTarget::ConfigInput)?
+        .iter()
+        .filter(|f| f.relative_path.extension().map(|e| e == "ini") == Some(true))
+        .filter_map(|f| f.relative_path.file_stem())
+        .map(|f| f.to_string_lossy().to_string())
+        .collect())
+}
+
+pub fn optimize_input(
+    config: &OptimizerConfig,
+    sources: &ContentSources,
+    bound_profile: Option<&str>,
+    progress: &mut ProgressTracker,
+) -> io::Result<()> {
+    let input_files = sources.files(PackTarget::ConfigInput)?;
+    progress.add_total_files(count_bundled_files(&input_files, &|_| true));
+    load_bundled_dir(&input_files, config.get_input_profile_folder()?, progress)?;
+
+    let profile = match bound_profile {
+        Some(profile) => profile,
+        None => return Ok(()),
+    };
+    if !input_profile_names(sources)?.iter().any(|p| p == profile) {
+        return Err(io_error!(NotFound, "Unknown input profile: {}", profile));
+    }
+    progress.check_cancelled()?;
+    let game_settings_path = config.get_game_settings_path()?;
+    let mut game_settings = match game_settings_path.is_file() {
+        true => Ini::load_from_file_noescape(game_settings_path.as_path()).map_err(|e| {
+            io_error!(
+                InvalidData,
+                "Unable to parse settings file {}: {}",
+                game_settings_path.to_string_lossy(),
+                e
+            )
+        })?,
+        false => Ini::new(),
+    };
+    log::info!("Binding input profile {} to player 1", profile);
+    set_per_game_setting(
+        &mut game_settings,
+        "Controls",
+        "player_0_profile_name",
+        profile,
+    );
+    if let Some(parent) = game_settings_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut contents = vec![];
+    game_settings
+        .write_to_policy(&mut contents, ini::EscapePolicy::Nothing)
+        .ok()
+        .ok_or(io_error!(
+            NotFound,
+            "Unable to save settings file: {}",
+            game_settings_path.to_string_lossy()
+        ))?;
+    write_atomic(game_settings_path.as_path(), &contents)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptimizationStep {
+    Clean(Vec<AdvancedOption>),
+    Optimize(Optimization),
+    // the per-user part of the mods optimization: arcropolis config and verification
+    UserMods(Vec<String>),
+    // the WebService part of the settings optimization, its own step so a failure doesn't hide the settings
+    WebService,
+}
+
+impl std::fmt::Display for OptimizationStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OptimizationStep::Clean(options) => write!(f, "Clean ({:?})", options),
+            OptimizationStep::Optimize(optimization) => write!(f, "{}", optimization),
+            OptimizationStep::UserMods(_) => write!(f, "ARCropolis Config"),
+            OptimizationStep::WebService => write!(f, "Web Service"),
+        }
+    }
+}
+
+impl OptimizationStep {
+    // clean runs first so it never removes freshly installed files, settings run last
+    fn order(&self) -> u8 {
+        match self {
+            OptimizationStep::Clean(_) => 0,
+            OptimizationStep::Optimize(Optimization::Mods(_)) => 1,
+            OptimizationStep::UserMods(_) => 2,
+            OptimizationStep::Optimize(Optimization::Save) => 3,
+            OptimizationStep::Optimize(Optimization::Input(_)) => 4,
+            OptimizationStep::Optimize(Optimization::Settings(_)) => 5,
+            OptimizationStep::WebService => 6,
+        }
+    }
+
+    // per-user steps run once for every user of a job, the others once for all of them
+    pub fn is_per_user(&self) -> bool {
+        matches!(
+            self,
+            OptimizationStep::UserMods(_) | OptimizationStep::Optimize(Optimization::Save)
+        )
+    }
+
+    // the optimization a user has once this step completed for them
+    pub fn applied_optimization(&self) -> Option<Optimization> {
+        match self {
+            OptimizationStep::Clean(_)
+            | OptimizationStep::Optimize(Optimization::Mods(_))
+            | OptimizationStep::WebService => None,
+            OptimizationStep::Optimize(optimization) => Some(optimization.clone()),
+            OptimizationStep::UserMods(selected_mods) => {
+                Some(Optimization::Mods(selected_mods.clone()))
+            }
+        }
+    }
+}
+
+pub fn plan_steps(
+    optimizations: Vec<(Optimization, Vec<AdvancedOption>)>,
+) -> Vec<OptimizationStep> {
+    let mut clean_options: Vec<AdvancedOption> = vec![];
+    let mut steps = vec![];
+    for (optimization, advanced_options) in optimizations {
+        for option in advanced_options {
+            if !clean_options.contains(&option) {
+                clean_options.push(option);
+            }
+        }
+        match &optimization {
+            Optimization::Mods(selected_mods) => {
+                steps.push(OptimizationStep::UserMods(selected_mods.clone()))
+            }
+            Optimization::Settings(_) => steps.push(OptimizationStep::WebService),
+            _ => {}
+        }
+        steps.push(OptimizationStep::Optimize(optimization));
+    }
+    if !clean_options.is_empty() {
+        steps.push(OptimizationStep::Clean(clean_options));
+    }
+    steps.sort_by_key(|s| s.order());
+    steps
+}
+
+// shared steps get every user of the job, per-user steps a single one
+pub fn run_step(
+    config: &OptimizerConfig,
+    sources: &ContentSources,
+    user_profiles: &[UserProfile],
+    step: &OptimizationStep,
+    progress: &mut ProgressTracker,
+) -> io::Result<OptimizationReport> {
+    // the emulator has a single web service login, it goes to the first user
+    let user_profile = user_profiles
+        .first()
+        .ok_or(io_error!(InvalidInput, "No user selected"))?;
+    let mut report = OptimizationReport::default();
+    match step {
+        OptimizationStep::Clean(advanced_options) => {
+            clean_mods(config, advanced_options, progress)?
+        }
+        OptimizationStep::Optimize(Optimization::Settings(preset)) => {
+            let preset = presets::get_settings_preset(preset)?;
+            report.settings_plan = Some(optimize_settings(config, sources, preset, progress)?);
+        }
+        OptimizationStep::WebService => {
+            report.web_service = web_service::apply_web_service(config, user_profile)?
+        }
+        OptimizationStep::Optimize(Optimization::Mods(selected_mods)) => {
+            optimize_mods(config, sources, selected_mods, progress)?;
+        }
+        OptimizationStep::UserMods(selected_mods) => {
+            optimize_arc_config(config, sources, user_profile, progress)?;
+            report.verification =
+                verify::verify_mods(config, sources, user_profile, selected_mods)?;
+        }
+        OptimizationStep::Optimize(Optimization::Save) => {
+            optimize_save(config, sources, user_profile, progress)?
+        }
+        OptimizationStep::Optimize(Optimization::Input(profile)) => {
+            optimize_input(config, sources, profile.as_deref(), progress)?
+        }
+    }
+    if let Some(optimization) = step.applied_optimization() {
+        report.content_version = Some(sources.version(optimization.targets()));
+    }
+    report.extraction = progress.extraction();
+    Ok(report)
+}
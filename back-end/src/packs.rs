@@ -0,0 +1,492 @@
+use crate::bundle::{self, BundledFile};
+use crate::config::OptimizerConfig;
+use crate::utils::{io_error, sha256_hex};
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use zip::ZipArchive;
+
+const MANIFEST_FILE: &str = "manifest.json";
+// hex encoded ed25519 signature over the exact bytes of the manifest
+const SIGNATURE_FILE: &str = "manifest.sig";
+const PACK_EXTENSION: &str = "zip";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PackTarget {
+    ConfigCustom,
+    ConfigInput,
+    SdmcSkyline,
+    ArcMods,
+    ArcConfig,
+    Save,
+}
+
+impl PackTarget {
+    pub fn embedded_tree(&self) -> &'static str {
+        match self {
+            PackTarget::ConfigCustom => bundle::SSBU_SETTINGS_TREE,
+            PackTarget::ConfigInput => bundle::INPUT_PROFILES_TREE,
+            PackTarget::SdmcSkyline => bundle::SKYLINE_TREE,
+            PackTarget::ArcMods => bundle::ARC_MODS_TREE,
+            PackTarget::ArcConfig => bundle::ARC_CONFIG_TREE,
+            PackTarget::Save => bundle::SAVE_TREE,
+        }
+    }
+}
+
+// a folder of the pack archive and the location it is installed to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackTree {
+    pub path: String,
+    pub target: PackTarget,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackManifest {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    pub trees: Vec<PackTree>,
+    // archive path to sha256, required for every installed file of a signed pack
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+    // releases of skyline plugins, so installed plugins are identified by hash instead of name
+    #[serde(default)]
+    pub plugins: Vec<KnownPlugin>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownPlugin {
+    // sha256 of the released .nro
+    pub sha256: String,
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedKey {
+    pub name: String,
+    // hex encoded ed25519 public key
+    pub public_key: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackSecuritySettings {
+    pub trusted_keys: Vec<TrustedKey>,
+    // unsigned packs are only loaded (with a warning) when this is set
+    pub allow_unsigned: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum PackSignature {
+    Unsigned,
+    Trusted(String),
+    // not loaded, with the reason it was rejected
+    Rejected(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DataPack {
+    pub file_name: String,
+    pub path: PathBuf,
+    pub manifest: PackManifest,
+    pub signature: PackSignature,
+}
+
+fn zip_error(e: zip::result::ZipError) -> io::Error {
+    io_error!(InvalidData, "Unable to read data pack: {}", e)
+}
+
+pub fn get_packs_folder(config: &OptimizerConfig) -> PathBuf {
+    config.app_data_folder.join("packs")
+}
+
+fn read_archive_file(
+    archive: &mut ZipArchive<fs::File>,
+    name: &str,
+) -> io::Result<Option<Vec<u8>>> {
+    let mut file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(zip_error(e)),
+    };
+    let mut contents = vec![];
+    file.read_to_end(&mut contents)?;
+    Ok(Some(contents))
+}
+
+fn verify_signature(
+    manifest: &[u8],
+    signature: &[u8],
+    security: &PackSecuritySettings,
+) -> io::Result<PackSignature> {
+    let signature = hex::decode(String::from_utf8_lossy(signature).trim())
+        .ok()
+        .and_then(|s| Signature::from_slice(&s).ok())
+        .ok_or(io_error!(InvalidData, "Invalid data pack signature"))?;
+    for key in security.trusted_keys.iter() {
+        let verifying_key = match hex::decode(key.public_key.trim())
+            .ok()
+            .and_then(|k| <[u8; 32]>::try_from(k).ok())
+            .and_then(|k| VerifyingKey::from_bytes(&k).ok())
+        {
+            Some(verifying_key) => verifying_key,
+            None => {
+                log::warn!("Skipping invalid trusted key: {}", key.name);
+                continue;
+            }
+        };
+        if verifying_key.verify_strict(manifest, &signature).is_ok() {
+            return Ok(PackSignature::Trusted(key.name.clone()));
+        }
+    }
+    Err(io_error!(
+        PermissionDenied,
+        "Data pack is not signed by a trusted key"
+    ))
+}
+
+fn load_pack(
+    path: &Path,
+    security: &PackSecuritySettings,
+) -> io::Result<(DataPack, ZipArchive<fs::File>)> {
+    let mut archive = ZipArchive::new(fs::File::open(path)?).map_err(zip_error)?;
+    let manifest_data = read_archive_file(&mut archive, MANIFEST_FILE)?.ok_or(io_error!(
+        NotFound,
+        "Data pack has no {}",
+        MANIFEST_FILE
+    ))?;
+    let manifest: PackManifest = serde_json::from_slice(&manifest_data)
+        .map_err(|e| io_error!(InvalidData, "Unable to read data pack manifest: {}", e))?;
+    let signature = match read_archive_file(&mut archive, SIGNATURE_FILE)? {
+        Some(signature) => verify_signature(&manifest_data, &signature, security)?,
+        None if security.allow_unsigned => {
+            log::warn!("Loading unsigned data pack: {}", path.to_string_lossy());
+            PackSignature::Unsigned
+        }
+        None => {
+            return Err(io_error!(
+                PermissionDenied,
+                "Data pack is not signed: {}",
+                path.to_string_lossy()
+            ))
+        }
+    };
+    let pack = DataPack {
+        file_name: pack_file_name(path),
+        path: path.to_path_buf(),
+        manifest,
+        signature,
+    };
+    Ok((pack, archive))
+}
+
+fn pack_file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+// sorted by file name, a pack overrides the targets of the packs before it.
+// Rejected packs are listed too, without an archive
+fn scan_packs(
+    config: &OptimizerConfig,
+) -> io::Result<Vec<(DataPack, Option<ZipArchive<fs::File>>)>> {
+    let packs_folder = get_packs_folder(config);
+    if !packs_folder.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut packs = vec![];
+    for entry in fs::read_dir(packs_folder)? {
+        let path = entry?.path();
+        if path.extension().map(|e| e == PACK_EXTENSION) != Some(true) {
+            continue;
+        }
+        match load_pack(path.as_path(), &config.local_data.pack_security) {
+            Ok((pack, archive)) => packs.push((pack, Some(archive))),
+            Err(e) => {
+                log::warn!("Rejecting data pack {}: {}", path.to_string_lossy(), e);
+                let pack = DataPack {
+                    file_name: pack_file_name(path.as_path()),
+                    manifest: PackManifest {
+                        name: pack_file_name(path.as_path()),
+                        ..PackManifest::default()
+                    },
+                    path,
+                    signature: PackSignature::Rejected(e.to_string()),
+                };
+                packs.push((pack, None));
+            }
+        }
+    }
+    packs.sort_by(|(a, _), (b, _)| a.file_name.cmp(&b.file_name));
+    Ok(packs)
+}
+
+pub fn list_packs(config: &OptimizerConfig) -> io::Result<Vec<DataPack>> {
+    Ok(scan_packs(config)?.into_iter().map(|(p, _)| p).collect())
+}
+
+pub fn import_pack(config: &OptimizerConfig, archive_path: &Path) -> io::Result<DataPack> {
+    let (pack, _) = load_pack(archive_path, &config.local_data.pack_security)?;
+    let file_name = archive_path.file_name().ok_or(io_error!(
+        InvalidInput,
+        "Invalid data pack path: {}",
+        archive_path.to_string_lossy()
+    ))?;
+    let packs_folder = get_packs_folder(config);
+    fs::create_dir_all(packs_folder.as_path())?;
+    log::info!(
+        "Importing data pack {} ({})",
+        pack.manifest.name,
+        pack.manifest.version
+    );
+    let target = packs_folder.join(file_name);
+    fs::copy(archive_path, target.as_path())?;
+    Ok(DataPack {
+        path: target,
+        ..pack
+    })
+}
+
+pub fn remove_pack(config: &OptimizerConfig, file_name: &str) -> io::Result<()> {
+    let mut components = Path::new(file_name).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        return Err(io_error!(
+            InvalidInput,
+            "Invalid data pack name: {}",
+            file_name
+        ));
+    }
+    log::info!("Removing data pack: {}", file_name);
+    fs::remove_file(get_packs_folder(config).join(file_name))
+}
+
+struct LoadedPack {
+    pack: DataPack,
+    archive: RefCell<ZipArchive<fs::File>>,
+    // trees whose files all matched the manifest hashes
+    verified_trees: RefCell<BTreeSet<String>>,
+}
+
+enum ContentOrigin<'a> {
+    Embedded(&'static BundledFile),
+    // entry name and its hash from the manifest
+    Pack(&'a LoadedPack, String, Option<&'a str>),
+}
+
+pub struct ContentFile<'a> {
+    // relative to the root of the target
+    pub relative_path: PathBuf,
+    pub size: u64,
+    origin: ContentOrigin<'a>,
+}
+
+impl ContentFile<'_> {
+    pub fn source(&self) -> String {
+        match &self.origin {
+            ContentOrigin::Embedded(file) => file.path.to_string(),
+            ContentOrigin::Pack(pack, name, _) => format!("{}:{}", pack.pack.file_name, name),
+        }
+    }
+
+    pub fn file_name(&self) -> String {
+        self.relative_path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    pub fn reader(&self) -> io::Result<Box<dyn Read + '_>> {
+        match &self.origin {
+            ContentOrigin::Embedded(file) => Ok(Box::new(file.reader()?)),
+            ContentOrigin::Pack(..) => Ok(Box::new(io::Cursor::new(self.contents()?))),
+        }
+    }
+
+    pub fn contents(&self) -> io::Result<Vec<u8>> {
+        match &self.origin {
+            ContentOrigin::Embedded(file) => file.contents(),
+            ContentOrigin::Pack(pack, name, sha256) => {
+                let mut archive = pack.archive.borrow_mut();
+                let mut file = archive.by_name(name).map_err(zip_error)?;
+                let mut contents = Vec::with_capacity(self.size as usize);
+                file.read_to_end(&mut contents)?;
+                if let Some(sha256) = sha256 {
+                    if !sha256.eq_ignore_ascii_case(&sha256_hex(&contents)) {
+                        return Err(io_error!(
+                            InvalidData,
+                            "Data pack file does not match its manifest hash: {}",
+                            self.source()
+                        ));
+                    }
+                }
+                Ok(contents)
+            }
+        }
+    }
+
+    pub fn sha256(&self) -> io::Result<String> {
+        match &self.origin {
+            ContentOrigin::Embedded(file) => Ok(file.sha256.to_string()),
+            ContentOrigin::Pack(_, _, Some(sha256)) => Ok(sha256.to_lowercase()),
+            ContentOrigin::Pack(..) => Ok(sha256_hex(&self.contents()?)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentVersion {
+    // the app version for embedded content, the pack name and version otherwise
+    pub version: String,
+    pub content_hash: String,
+}
+
+// the content installed by the optimizer, taken from data packs when they provide a target
+pub struct ContentSources {
+    packs: Vec<LoadedPack>,
+}
+
+impl ContentSources {
+    pub fn load(config: &OptimizerConfig) -> Self {
+        let mut packs = vec![];
+        for (pack, archive) in scan_packs(config).unwrap_or_default() {
+            if let Some(archive) = archive {
+                log::info!(
+                    "Using data pack {} ({})",
+                    pack.manifest.name,
+                    pack.manifest.version
+                );
+                packs.push(LoadedPack {
+                    pack,
+                    archive: RefCell::new(archive),
+                    verified_trees: RefCell::default(),
+                })
+            }
+        }
+        ContentSources { packs }
+    }
+
+    fn provider(&self, target: PackTarget) -> Option<(&LoadedPack, &PackTree)> {
+        self.packs.iter().rev().find_map(|p| {
+            p.pack
+                .manifest
+                .trees
+                .iter()
+                .find(|t| t.target == target)
+                .map(|t| (p, t))
+        })
+    }
+
+    // changes whenever the content of any of the targets does, without reading any file
+    pub fn version(&self, targets: &[PackTarget]) -> ContentVersion {
+        let mut versions: Vec<String> = vec![];
+        let mut hashes = String::new();
+        for target in targets {
+            let (version, hash) = match self.provider(*target) {
+                Some((pack, _)) => (
+                    format!("{} {}", pack.pack.manifest.name, pack.pack.manifest.version),
+                    sha256_hex(&serde_json::to_vec(&pack.pack.manifest).unwrap_or_default()),
+                ),
+                None => (
+                    format!("v{}", bundle::BUNDLE_VERSION),
+                    bundle::tree_hash(target.embedded_tree()).to_string(),
+                ),
+            };
+            if !versions.contains(&version) {
+                versions.push(version);
+            }
+            hashes.push_str(&format!("{:?} {}\n", target, hash));
+        }
+        ContentVersion {
+            version: versions.join(", "),
+            content_hash: sha256_hex(hashes.as_bytes()),
+        }
+    }
+
+    pub fn files(&self, target: PackTarget) -> io::Result<Vec<ContentFile<'_>>> {
+        let (pack, tree) = match self.provider(target) {
+            Some(provider) => provider,
+            None => {
+                let tree = target.embedded_tree();
+                return Ok(bundle::files(Path::new(tree))
+                    .iter()
+                    .map(|f| ContentFile {
+                        relative_path: f.path_in(tree).to_path_buf(),
+                        size: f.size,
+                        origin: ContentOrigin::Embedded(f),
+                    })
+                    .collect());
+            }
+        };
+
+        let tree_path = Path::new(tree.path.trim_matches('/'));
+        let mut files = vec![];
+        let mut archive = pack.archive.borrow_mut();
+        for i in 0..archive.len() {
+            let file = archive.by_index(i).map_err(zip_error)?;
+            if file.is_dir() {
+                continue;
+            }
+            let relative_path = match file.enclosed_name() {
+                Some(p) => match p.strip_prefix(tree_path) {
+                    Ok(r) if r.components().next().is_some() => r.to_path_buf(),
+                    _ => continue,
+                },
+                None => {
+                    log::warn!("Skipping unsafe data pack entry: {}", file.name());
+                    continue;
+                }
+            };
+            let sha256 = pack
+                .pack
+                .manifest
+                .files
+                .get(file.name())
+                .map(|h| h.as_str());
+            let hashes_required = pack.pack.signature != PackSignature::Unsigned
+                || !pack.pack.manifest.files.is_empty();
+            if sha256.is_none() && hashes_required {
+                return Err(io_error!(
+                    InvalidData,
+                    "Data pack {} has a file missing from its manifest: {}",
+                    pack.pack.file_name,
+                    file.name()
+                ));
+            }
+            files.push(ContentFile {
+                relative_path,
+                size: file.size(),
+                origin: ContentOrigin::Pack(pack, file.name().to_string(), sha256),
+            });
+        }
+        drop(archive);
+        files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+
+        // the whole tree is checked before anything of it is installed
+        if !pack.verified_trees.borrow().contains(&tree.path) {
+            for file in files.iter() {
+                if let ContentOrigin::Pack(_, _, Some(_)) = file.origin {
+                    file.contents().map_err(|e| {
+                        io_error!(
+                            InvalidData,
+                            "Data pack {} failed verification, nothing was installed from it: {}",
+                            pack.pack.file_name,
+                            e
+                        )
+                    })?;
+                }
+            }
+            pack.verified_trees.borrow_mut().insert(tree.path.clone());
+        }
+        Ok(files)
+    }
+}
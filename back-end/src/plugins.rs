@@ -0,0 +1,178 @@
+use crate::bundle;
+use crate::config::OptimizerConfig;
+use crate::packs::{self, KnownPlugin, PackSignature};
+use crate::utils::{io_error, sha256_file};
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+const PLUGINS_PATH: &str = "romfs/skyline/plugins";
+// outside of the plugins folder so skyline never loads them
+const DISABLED_PLUGINS_PATH: &str = "romfs/skyline/disabled_plugins";
+const PLUGIN_EXTENSION: &str = "nro";
+
+// (file name, display name), only used when the hash is unknown
+static KNOWN_PLUGINS: &[(&str, &str)] = &[
+    ("libarcropolis.nro", "ARCropolis"),
+    ("libtraining_modpack.nro", "Training Modpack"),
+    ("liblatency_slider_de.nro", "Latency Slider"),
+    ("libnro_hook.nro", "NRO Hook"),
+    ("libsmashline_plugin.nro", "Smashline"),
+    ("libone_slot_eff.nro", "One Slot Effects"),
+    ("libcss_preserve.nro", "CSS Preserve"),
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginInfo {
+    pub file_name: String,
+    pub name: String,
+    // only known when the hash matches a known plugin version
+    pub version: Option<String>,
+    pub sha256: String,
+    pub size: u64,
+    pub enabled: bool,
+}
+
+fn get_plugin_name(file_name: &str) -> String {
+    KNOWN_PLUGINS
+        .iter()
+        .find(|(f, _)| f.eq_ignore_ascii_case(file_name))
+        .map(|(_, n)| n.to_string())
+        .unwrap_or(file_name.to_string())
+}
+
+// the release tables of the data packs first, the last pack taking precedence like for content,
+// then the files bundled with this build
+fn known_plugin_versions(config: &OptimizerConfig) -> io::Result<Vec<KnownPlugin>> {
+    let mut known_versions: Vec<KnownPlugin> = packs::list_packs(config)?
+        .into_iter()
+        .rev()
+        .filter(|p| !matches!(p.signature, PackSignature::Rejected(_)))
+        .flat_map(|p| p.manifest.plugins)
+        .collect();
+    known_versions.extend(
+        bundle::files(&Path::new(bundle::SKYLINE_TREE).join(PLUGINS_PATH))
+            .iter()
+            .map(|f| KnownPlugin {
+                sha256: f.sha256.to_string(),
+                name: get_plugin_name(f.file_name()),
+                version: format!("bundled with optimizer v{}", env!("CARGO_PKG_VERSION")),
+            }),
+    );
+    Ok(known_versions)
+}
+
+fn validate_plugin_file_name(file_name: &str) -> io::Result<()> {
+    let path = Path::new(file_name);
+    let mut components = path.components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None)
+            if path.extension().map(|e| e == PLUGIN_EXTENSION) == Some(true) =>
+        {
+            Ok(())
+        }
+        _ => Err(io_error!(
+            InvalidInput,
+            "Invalid skyline plugin file name: {}",
+            file_name
+        )),
+    }
+}
+
+pub fn get_plugin_folder(config: &OptimizerConfig, enabled: bool) -> io::Result<PathBuf> {
+    Ok(config.get_skyline_folder()?.join(match enabled {
+        true => PLUGINS_PATH,
+        false => DISABLED_PLUGINS_PATH,
+    }))
+}
+
+pub fn list_plugins(config: &OptimizerConfig) -> io::Result<Vec<PluginInfo>> {
+    let known_versions = known_plugin_versions(config)?;
+    let mut plugins = vec![];
+    for enabled in [true, false] {
+        let plugin_folder = get_plugin_folder(config, enabled)?;
+        if !plugin_folder.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(plugin_folder)? {
+            let path = entry?.path();
+            if !path.is_file() || path.extension().map(|e| e == PLUGIN_EXTENSION) != Some(true) {
+                continue;
+            }
+            let file_name = path
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let sha256 = sha256_file(path.as_path())?;
+            let known_version = known_versions
+                .iter()
+                .find(|k| k.sha256.eq_ignore_ascii_case(&sha256));
+            plugins.push(PluginInfo {
+                name: known_version
+                    .map(|k| k.name.clone())
+                    .unwrap_or(get_plugin_name(&file_name)),
+                version: known_version.map(|k| k.version.clone()),
+                sha256,
+                size: fs::metadata(path.as_path())?.len(),
+                enabled,
+                file_name,
+            });
+        }
+    }
+    plugins.sort_by_key(|p| p.name.to_lowercase());
+    Ok(plugins)
+}
+
+pub fn set_plugin_enabled(
+    config: &OptimizerConfig,
+    file_name: &str,
+    enabled: bool,
+) -> io::Result<()> {
+    validate_plugin_file_name(file_name)?;
+    let from = get_plugin_folder(config, !enabled)?.join(file_name);
+    let to_folder = get_plugin_folder(config, enabled)?;
+    if to_folder.join(file_name).is_file() {
+        log::info!(
+            "Skyline plugin {} already has enabled state: {}",
+            file_name,
+            enabled
+        );
+        return Ok(());
+    }
+    if !from.is_file() {
+        return Err(io_error!(
+            NotFound,
+            "Unable to find skyline plugin: {}",
+            file_name
+        ));
+    }
+    log::info!(
+        "{} skyline plugin: {}",
+        if enabled { "Enabling" } else { "Disabling" },
+        file_name
+    );
+    fs::create_dir_all(to_folder.as_path())?;
+    fs::rename(from, to_folder.join(file_name))
+}
+
+pub fn remove_plugin(config: &OptimizerConfig, file_name: &str) -> io::Result<()> {
+    validate_plugin_file_name(file_name)?;
+    let mut removed = false;
+    for enabled in [true, false] {
+        let plugin_path = get_plugin_folder(config, enabled)?.join(file_name);
+        if plugin_path.is_file() {
+            log::info!("Removing skyline plugin: {}", plugin_path.to_string_lossy());
+            fs::remove_file(plugin_path)?;
+            removed = true;
+        }
+    }
+    match removed {
+        true => Ok(()),
+        false => Err(io_error!(
+            NotFound,
+            "Unable to find skyline plugin: {}",
+            file_name
+        )),
+    }
+}
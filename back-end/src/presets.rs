@@ -0,0 +1,92 @@
+use crate::utils::{io_error, set_per_game_setting};
+use ini::Ini;
+use serde::Serialize;
+use std::io;
+
+// (section, key, value) applied on top of the bundled per-game settings
+type SettingOverride = (&'static str, &'static str, &'static str);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingsPreset {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    #[serde(skip)]
+    overrides: &'static [SettingOverride],
+}
+
+pub static SETTINGS_PRESETS: &[SettingsPreset] = &[
+    SettingsPreset {
+        id: "competitive",
+        name: "Competitive",
+        description: "Lowest input latency for netplay and tournaments at native resolution",
+        overrides: &[
+            ("Renderer", "resolution_setup", "2"),
+            ("Renderer", "vsync_mode", "0"),
+            ("Renderer", "gpu_accuracy", "0"),
+            ("Renderer", "use_asynchronous_shaders", "true"),
+            ("Renderer", "use_fast_gpu_time", "true"),
+            ("Core", "use_multi_core", "true"),
+        ],
+    },
+    SettingsPreset {
+        id: "low_end",
+        name: "Low-End Laptop",
+        description: "Sub-native resolution with FSR upscaling for integrated graphics",
+        overrides: &[
+            ("Renderer", "resolution_setup", "1"),
+            ("Renderer", "scaling_filter", "5"),
+            ("Renderer", "anti_aliasing", "0"),
+            ("Renderer", "max_anisotropy", "1"),
+            ("Renderer", "gpu_accuracy", "0"),
+            ("Renderer", "use_asynchronous_shaders", "true"),
+            ("Renderer", "use_fast_gpu_time", "true"),
+            ("Core", "use_multi_core", "true"),
+        ],
+    },
+    SettingsPreset {
+        id: "steam_deck",
+        name: "Steam Deck Handheld",
+        description: "Handheld mode at native resolution tuned for the Steam Deck screen",
+        overrides: &[
+            ("System", "use_docked_mode", "false"),
+            ("Renderer", "resolution_setup", "2"),
+            ("Renderer", "scaling_filter", "5"),
+            ("Renderer", "vsync_mode", "2"),
+            ("Renderer", "gpu_accuracy", "0"),
+            ("Renderer", "use_asynchronous_shaders", "true"),
+            ("Renderer", "use_fast_gpu_time", "true"),
+            ("Core", "use_multi_core", "true"),
+        ],
+    },
+    SettingsPreset {
+        id: "recording",
+        name: "High Quality Recording",
+        description: "Higher resolution and accuracy for capturing footage, at the cost of latency",
+        overrides: &[
+            ("Renderer", "resolution_setup", "4"),
+            ("Renderer", "scaling_filter", "1"),
+            ("Renderer", "anti_aliasing", "2"),
+            ("Renderer", "max_anisotropy", "5"),
+            ("Renderer", "vsync_mode", "2"),
+            ("Renderer", "gpu_accuracy", "1"),
+            ("Renderer", "use_asynchronous_shaders", "false"),
+            ("Core", "use_multi_core", "true"),
+        ],
+    },
+];
+
+impl SettingsPreset {
+    pub fn apply(&self, game_settings: &mut Ini) {
+        for (section, key, value) in self.overrides {
+            set_per_game_setting(game_settings, section, key, value);
+        }
+    }
+}
+
+pub fn get_settings_preset(id: &str) -> io::Result<&'static SettingsPreset> {
+    SETTINGS_PRESETS
+        .iter()
+        .find(|p| p.id == id)
+        .ok_or(io_error!(NotFound, "Unknown settings preset: {}", id))
+}
@@ -0,0 +1,214 @@
+use crate::config::OptimizerConfig;
+use crate::utils::{dir_stats, format_timestamp, io_error, unix_time, DirStats};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+const METADATA_FILE: &str = "quarantine.json";
+const DATA_FOLDER: &str = "data";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuarantineRetention {
+    pub max_entries: usize,
+    pub max_age_days: u64,
+}
+
+impl Default for QuarantineRetention {
+    fn default() -> Self {
+        QuarantineRetention {
+            max_entries: 10,
+            max_age_days: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineMetadata {
+    pub label: String,
+    // seconds since the unix epoch
+    pub created: u64,
+    pub original_path: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantineEntry {
+    pub id: String,
+    pub metadata: QuarantineMetadata,
+    pub stats: DirStats,
+}
+
+pub fn get_quarantine_folder(config: &OptimizerConfig) -> PathBuf {
+    config.app_data_folder.join("quarantine")
+}
+
+fn copy_dir(source: &Path, target: &Path) -> io::Result<()> {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target.join(entry.file_name()))?;
+        } else {
+            fs::copy(entry.path(), target.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+// rename fails across drives (sdmc on another disk than app data), so fall back to copying
+fn move_dir(source: &Path, target: &Path) -> io::Result<()> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(source, target).is_ok() {
+        return Ok(());
+    }
+    log::info!(
+        "Unable to rename {}, copying instead",
+        source.to_string_lossy()
+    );
+    copy_dir(source, target)?;
+    fs::remove_dir_all(source)
+}
+
+fn load_entry(path: &Path) -> io::Result<QuarantineEntry> {
+    let metadata: QuarantineMetadata =
+        serde_json::from_reader(fs::File::open(path.join(METADATA_FILE))?)
+            .map_err(|e| io_error!(InvalidData, "Unable to read quarantine metadata: {}", e))?;
+    Ok(QuarantineEntry {
+        id: path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        metadata,
+        stats: dir_stats(&path.join(DATA_FOLDER))?,
+    })
+}
+
+fn move_to_quarantine(
+    config: &OptimizerConfig,
+    source: &Path,
+    label: &str,
+) -> io::Result<QuarantineEntry> {
+    let metadata = QuarantineMetadata {
+        label: label.to_string(),
+        created: unix_time(SystemTime::now()),
+        original_path: source.to_path_buf(),
+    };
+    let label_name: String = label
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let quarantine_folder = get_quarantine_folder(config);
+    let mut entry_path = quarantine_folder.join(format!(
+        "{}_{}",
+        format_timestamp(metadata.created),
+        label_name
+    ));
+    let mut suffix = 1;
+    while entry_path.exists() {
+        suffix += 1;
+        entry_path = quarantine_folder.join(format!(
+            "{}_{}_{}",
+            format_timestamp(metadata.created),
+            label_name,
+            suffix
+        ));
+    }
+    log::info!(
+        "Quarantining {} to: {}",
+        source.to_string_lossy(),
+        entry_path.to_string_lossy()
+    );
+    fs::create_dir_all(entry_path.as_path())?;
+    serde_json::to_writer_pretty(fs::File::create(entry_path.join(METADATA_FILE))?, &metadata)
+        .map_err(|e| io_error!(InvalidData, "Unable to write quarantine metadata: {}", e))?;
+    move_dir(source, &entry_path.join(DATA_FOLDER))?;
+    load_entry(entry_path.as_path())
+}
+
+pub fn quarantine_dir(
+    config: &OptimizerConfig,
+    source: &Path,
+    label: &str,
+) -> io::Result<QuarantineEntry> {
+    let entry = move_to_quarantine(config, source, label)?;
+    apply_retention(config, &entry.id)?;
+    Ok(entry)
+}
+
+pub fn list_quarantine(config: &OptimizerConfig) -> io::Result<Vec<QuarantineEntry>> {
+    let quarantine_folder = get_quarantine_folder(config);
+    if !quarantine_folder.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut entries = vec![];
+    for entry in fs::read_dir(quarantine_folder)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        match load_entry(path.as_path()) {
+            Ok(e) => entries.push(e),
+            Err(e) => log::warn!(
+                "Skipping invalid quarantine entry {}: {}",
+                path.to_string_lossy(),
+                e
+            ),
+        }
+    }
+    entries.sort_by_key(|e| std::cmp::Reverse(e.metadata.created));
+    Ok(entries)
+}
+
+// the newest entry is never removed so a clean can always be undone
+fn apply_retention(config: &OptimizerConfig, keep_id: &str) -> io::Result<()> {
+    let retention = &config.local_data.quarantine_retention;
+    let now = unix_time(SystemTime::now());
+    let max_age = retention.max_age_days * SECONDS_PER_DAY;
+    for (i, entry) in list_quarantine(config)?.iter().enumerate() {
+        if entry.id == keep_id {
+            continue;
+        }
+        if i >= retention.max_entries || now.saturating_sub(entry.metadata.created) > max_age {
+            log::info!("Removing expired quarantine entry: {}", entry.id);
+            fs::remove_dir_all(get_quarantine_folder(config).join(&entry.id))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn restore_quarantine(config: &OptimizerConfig, id: &str) -> io::Result<()> {
+    let mut components = Path::new(id).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        return Err(io_error!(InvalidInput, "Invalid quarantine entry: {}", id));
+    }
+    let entry_path = get_quarantine_folder(config).join(id);
+    let entry = load_entry(entry_path.as_path())?;
+    let original_path = entry.metadata.original_path.as_path();
+    // whatever was installed since the clean is quarantined in turn instead of being overwritten
+    let replaced = match original_path.exists() {
+        true => Some(move_to_quarantine(
+            config,
+            original_path,
+            &format!("Before restoring {}", entry.metadata.label),
+        )?),
+        false => None,
+    };
+    log::info!(
+        "Restoring quarantine entry {} to: {}",
+        id,
+        original_path.to_string_lossy()
+    );
+    move_dir(&entry_path.join(DATA_FOLDER), original_path)?;
+    fs::remove_dir_all(entry_path)?;
+    match replaced {
+        Some(replaced) => apply_retention(config, &replaced.id),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,299 @@
+use crate::config::OptimizerConfig;
+use crate::profile::UserProfile;
+use crate::utils::{io_error, unix_time};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+const METADATA_FILE: &str = "metadata.json";
+const SAVE_PREFIX: &str = "save";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveMetadata {
+    pub user_name: String,
+    // seconds since the unix epoch
+    pub created: u64,
+    pub label: Option<String>,
+    pub app_version: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SaveSnapshot {
+    pub file_name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub metadata: SaveMetadata,
+}
+
+fn zip_error(e: zip::result::ZipError) -> io::Error {
+    io_error!(InvalidData, "Unable to process save archive: {}", e)
+}
+
+pub fn get_snapshot_folder(config: &OptimizerConfig) -> PathBuf {
+    config.app_data_folder.join("save_snapshots")
+}
+
+fn write_save_dir<W: Write + io::Seek>(
+    zip: &mut ZipWriter<W>,
+    save_path: &Path,
+    relative_path: &Path,
+) -> io::Result<()> {
+    for entry in fs::read_dir(save_path.join(relative_path))? {
+        let entry = entry?;
+        let entry_path = relative_path.join(entry.file_name());
+        let archive_path = Path::new(SAVE_PREFIX)
+            .join(entry_path.as_path())
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if entry.file_type()?.is_dir() {
+            zip.add_directory(archive_path, FileOptions::default())
+                .map_err(zip_error)?;
+            write_save_dir(zip, save_path, entry_path.as_path())?;
+        } else {
+            zip.start_file(archive_path, FileOptions::default())
+                .map_err(zip_error)?;
+            io::copy(&mut fs::File::open(entry.path())?, zip)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_metadata<R: Read + io::Seek>(archive: &mut ZipArchive<R>) -> io::Result<SaveMetadata> {
+    let metadata = archive.by_name(METADATA_FILE).map_err(zip_error)?;
+    serde_json::from_reader(metadata)
+        .map_err(|e| io_error!(InvalidData, "Unable to read save metadata: {}", e))
+}
+
+fn load_snapshot(path: &Path) -> io::Result<SaveSnapshot> {
+    let mut archive = ZipArchive::new(fs::File::open(path)?).map_err(zip_error)?;
+    Ok(SaveSnapshot {
+        file_name: path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path: path.to_path_buf(),
+        size: fs::metadata(path)?.len(),
+        metadata: read_metadata(&mut archive)?,
+    })
+}
+
+// exports into the snapshot library unless a destination is given
+// never replaces an existing snapshot, even one taken in the same second
+fn create_snapshot_file(
+    config: &OptimizerConfig,
+    user_profile: &UserProfile,
+    created: u64,
+) -> io::Result<(PathBuf, fs::File)> {
+    let snapshot_folder = get_snapshot_folder(config);
+    fs::create_dir_all(snapshot_folder.as_path())?;
+    let user_name: String = user_profile
+        .name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect();
+    let mut path = snapshot_folder.join(format!("{}_{}.zip", created, user_name));
+    let mut suffix = 1;
+    loop {
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path.as_path())
+        {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                suffix += 1;
+                path = snapshot_folder.join(format!("{}_{}_{}.zip", created, user_name, suffix));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+pub fn export_save(
+    config: &OptimizerConfig,
+    user_profile: &UserProfile,
+    label: Option<String>,
+    destination: Option<PathBuf>,
+) -> io::Result<SaveSnapshot> {
+    let save_path = config.get_save_folder(user_profile)?;
+    if !save_path.is_dir() {
+        return Err(io_error!(
+            NotFound,
+            "No save data found for user: {}",
+            user_profile.name
+        ));
+    }
+    let metadata = SaveMetadata {
+        user_name: user_profile.name.clone(),
+        created: unix_time(SystemTime::now()),
+        label,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+    let (destination, file) = match destination {
+        // the save dialog already asked before replacing an existing file
+        Some(d) => {
+            let file = fs::File::create(d.as_path())?;
+            (d, file)
+        }
+        None => create_snapshot_file(config, user_profile, metadata.created)?,
+    };
+    log::info!(
+        "Exporting save for user {} to: {}",
+        user_profile.name,
+        destination.to_string_lossy()
+    );
+
+    let mut zip = ZipWriter::new(file);
+    zip.start_file(METADATA_FILE, FileOptions::default())
+        .map_err(zip_error)?;
+    serde_json::to_writer_pretty(&mut zip, &metadata)
+        .map_err(|e| io_error!(InvalidData, "Unable to write save metadata: {}", e))?;
+    write_save_dir(&mut zip, save_path.as_path(), Path::new(""))?;
+    zip.finish().map_err(zip_error)?;
+    load_snapshot(destination.as_path())
+}
+
+pub fn import_save(
+    config: &OptimizerConfig,
+    user_profile: &UserProfile,
+    archive_path: &Path,
+) -> io::Result<()> {
+    let mut archive = ZipArchive::new(fs::File::open(archive_path)?).map_err(zip_error)?;
+    let metadata = read_metadata(&mut archive)?;
+    let save_path = config.get_save_folder(user_profile)?;
+    if save_path.is_dir() {
+        export_save(
+            config,
+            user_profile,
+            Some(format!("Before importing save from {}", metadata.user_name)),
+            None,
+        )?;
+    }
+    log::info!(
+        "Importing save from {} into user {}",
+        archive_path.to_string_lossy(),
+        user_profile.name
+    );
+    // unpacked next to the save and swapped in once complete, a failed import leaves it untouched
+    let staging_path = sibling_path(&save_path, "import")?;
+    if staging_path.exists() {
+        fs::remove_dir_all(staging_path.as_path())?;
+    }
+    if let Err(e) = extract_save(&mut archive, &staging_path) {
+        let _ = fs::remove_dir_all(staging_path.as_path());
+        return Err(e);
+    }
+    replace_save(&save_path, &staging_path)
+}
+
+fn sibling_path(path: &Path, suffix: &str) -> io::Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .ok_or(io_error!(InvalidInput, "Invalid save folder"))?;
+    Ok(path.with_file_name(format!("{}.{}", file_name.to_string_lossy(), suffix)))
+}
+
+fn extract_save<R: Read + io::Seek>(archive: &mut ZipArchive<R>, target: &Path) -> io::Result<()> {
+    fs::create_dir_all(target)?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(zip_error)?;
+        let relative_path = match file.enclosed_name() {
+            Some(p) => match p.strip_prefix(SAVE_PREFIX) {
+                Ok(r) if r.components().next().is_some() => r.to_path_buf(),
+                _ => continue,
+            },
+            None => {
+                log::warn!("Skipping unsafe archive entry: {}", file.name());
+                continue;
+            }
+        };
+        let path = target.join(relative_path);
+        if file.is_dir() {
+            fs::create_dir_all(path)?;
+        } else {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            io::copy(&mut file, &mut fs::File::create(path)?)?;
+        }
+    }
+    Ok(())
+}
+
+// the old save is only removed once the new one is in place, and put back if that fails
+fn replace_save(save_path: &Path, staging_path: &Path) -> io::Result<()> {
+    if let Some(parent) = save_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let old_path = sibling_path(save_path, "old")?;
+    if old_path.exists() {
+        fs::remove_dir_all(old_path.as_path())?;
+    }
+    let had_save = save_path.exists();
+    if had_save {
+        fs::rename(save_path, old_path.as_path())?;
+    }
+    if let Err(e) = fs::rename(staging_path, save_path) {
+        if had_save {
+            fs::rename(old_path.as_path(), save_path)?;
+        }
+        let _ = fs::remove_dir_all(staging_path);
+        return Err(e);
+    }
+    if had_save {
+        if let Err(e) = fs::remove_dir_all(old_path.as_path()) {
+            log::warn!(
+                "Unable to remove previous save {}: {}",
+                old_path.to_string_lossy(),
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn list_snapshots(config: &OptimizerConfig) -> io::Result<Vec<SaveSnapshot>> {
+    let snapshot_folder = get_snapshot_folder(config);
+    if !snapshot_folder.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut snapshots = vec![];
+    for entry in fs::read_dir(snapshot_folder)? {
+        let path = entry?.path();
+        if path.extension().map(|e| e == "zip") != Some(true) {
+            continue;
+        }
+        match load_snapshot(path.as_path()) {
+            Ok(s) => snapshots.push(s),
+            Err(e) => log::warn!(
+                "Skipping invalid save snapshot {}: {}",
+                path.to_string_lossy(),
+                e
+            ),
+        }
+    }
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.metadata.created));
+    Ok(snapshots)
+}
+
+pub fn delete_snapshot(config: &OptimizerConfig, file_name: &str) -> io::Result<()> {
+    let mut components = Path::new(file_name).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        return Err(io_error!(
+            InvalidInput,
+            "Invalid save snapshot name: {}",
+            file_name
+        ));
+    }
+    log::info!("Deleting save snapshot: {}", file_name);
+    fs::remove_file(get_snapshot_folder(config).join(file_name))
+}
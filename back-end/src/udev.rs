@@ -0,0 +1,254 @@
+use crate::utils::io_error;
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// official nintendo gamecube adapter, 057e:0337
+const GC_ADAPTER_VENDOR_ID: &str = "057e";
+const GC_ADAPTER_PRODUCT_ID: &str = "0337";
+// relative to the rules root, which is / outside of testing
+const RULES_FOLDERS: &[&str] = &["etc/udev/rules.d", "lib/udev/rules.d"];
+const RULE_FILE_NAME: &str = "51-gcadapter.rules";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UdevRuleStatus {
+    // the rules file granting access to the adapter, if any
+    pub rule_path: Option<PathBuf>,
+    pub rule: String,
+    pub install_script: String,
+}
+
+pub fn gc_adapter_rule() -> String {
+    format!(
+        "SUBSYSTEM==\"usb\", ATTRS{{idVendor}}==\"{}\", ATTRS{{idProduct}}==\"{}\", MODE=\"0666\", TAG+=\"uaccess\"\n",
+        GC_ADAPTER_VENDOR_ID, GC_ADAPTER_PRODUCT_ID
+    )
+}
+
+// a rule only counts when it matches the adapter and actually opens it up to the user
+fn is_gc_adapter_rule(line: &str) -> bool {
+    let line = line.trim().to_lowercase().replace(' ', "");
+    let matches_id = |attribute: &str, id: &str| {
+        [
+            format!("attrs{{{}}}==\"{}\"", attribute, id),
+            format!("attr{{{}}}==\"{}\"", attribute, id),
+        ]
+        .iter()
+        .any(|m| line.contains(m.as_str()))
+    };
+    !line.starts_with('#')
+        && matches_id("idvendor", GC_ADAPTER_VENDOR_ID)
+        && matches_id("idproduct", GC_ADAPTER_PRODUCT_ID)
+        && ["mode=", "tag+=\"uaccess\"", "group="]
+            .iter()
+            .any(|a| line.contains(a))
+}
+
+pub fn find_gc_adapter_rule(root: &Path) -> io::Result<Option<PathBuf>> {
+    for folder in RULES_FOLDERS {
+        let rules_path = root.join(folder);
+        if !rules_path.is_dir() {
+            continue;
+        }
+        let mut rules: Vec<PathBuf> = fs::read_dir(rules_path)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file() && p.extension().map(|e| e == "rules") == Some(true))
+            .collect();
+        rules.sort();
+        for rule_path in rules {
+            // unreadable rules files are skipped, udev can't be relied on to read them either
+            let contents = match fs::read_to_string(rule_path.as_path()) {
+                Ok(c) => c,
+                Err(e) => {
+                    log::warn!(
+                        "Unable to read udev rules {}: {}",
+                        rule_path.to_string_lossy(),
+                        e
+                    );
+                    continue;
+                }
+            };
+            if contents.lines().any(is_gc_adapter_rule) {
+                return Ok(Some(rule_path));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn shell_quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', "'\\''"))
+}
+
+pub fn gc_adapter_install_script(root: &Path) -> String {
+    let rules_path = root.join(RULES_FOLDERS[0]);
+    format!(
+        "#!/bin/sh\n\
+         set -e\n\
+         mkdir -p {}\n\
+         cat > {} <<'EOF'\n\
+         {}\
+         EOF\n\
+         udevadm control --reload-rules || true\n\
+         udevadm trigger || true\n",
+        shell_quote(&rules_path),
+        shell_quote(&rules_path.join(RULE_FILE_NAME)),
+        gc_adapter_rule()
+    )
+}
+
+pub fn gc_adapter_rule_status(root: &Path) -> io::Result<UdevRuleStatus> {
+    Ok(UdevRuleStatus {
+        rule_path: find_gc_adapter_rule(root)?,
+        rule: gc_adapter_rule(),
+        install_script: gc_adapter_install_script(root),
+    })
+}
+
+// pkexec shows a graphical password prompt, sudo needs a terminal so it is only suggested.
+// The script goes through stdin, a file in the user's app data could be swapped before it runs as root
+pub fn install_gc_adapter_rule(root: &Path) -> io::Result<PathBuf> {
+    log::info!("Installing GameCube adapter udev rule");
+    let mut child = Command::new("pkexec")
+        .arg("sh")
+        .arg("-s")
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            io_error!(
+                NotFound,
+                "Unable to run pkexec ({}), run the install script with sudo sh instead",
+                e
+            )
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(gc_adapter_install_script(root).as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io_error!(
+            PermissionDenied,
+            "Installing the udev rule failed ({}), run the install script with sudo sh instead",
+            status
+        ));
+    }
+    find_gc_adapter_rule(root)?.ok_or(io_error!(
+        NotFound,
+        "The udev rule was not found after installing it"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a fresh rules root under the system temp folder, removed when dropped
+    struct TempRoot(PathBuf);
+
+    impl TempRoot {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("udev-test-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(path.as_path());
+            fs::create_dir_all(path.as_path()).unwrap();
+            TempRoot(path)
+        }
+
+        fn write(&self, folder: &str, file_name: &str, contents: &[u8]) -> PathBuf {
+            let path = self.0.join(folder).join(file_name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path.as_path(), contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(self.0.as_path());
+        }
+    }
+
+    #[test]
+    fn generated_rule_matches() {
+        assert!(is_gc_adapter_rule(&gc_adapter_rule()));
+    }
+
+    #[test]
+    fn commented_rule_is_ignored() {
+        assert!(!is_gc_adapter_rule(&format!("# {}", gc_adapter_rule())));
+        assert!(!is_gc_adapter_rule(&format!("  #{}", gc_adapter_rule())));
+    }
+
+    #[test]
+    fn attr_and_attrs_both_match() {
+        assert!(is_gc_adapter_rule(
+            r#"SUBSYSTEM=="usb", ATTRS{idVendor}=="057e", ATTRS{idProduct}=="0337", MODE="0666""#
+        ));
+        assert!(is_gc_adapter_rule(
+            r#"SUBSYSTEM=="usb", ATTR{idVendor}=="057E", ATTR{idProduct}=="0337", TAG+="uaccess""#
+        ));
+        assert!(is_gc_adapter_rule(
+            r#"SUBSYSTEM=="usb", ATTR{idVendor} == "057e", ATTRS{idProduct} == "0337", GROUP="input""#
+        ));
+    }
+
+    #[test]
+    fn rule_without_access_is_ignored() {
+        assert!(!is_gc_adapter_rule(
+            r#"SUBSYSTEM=="usb", ATTRS{idVendor}=="057e", ATTRS{idProduct}=="0337""#
+        ));
+        assert!(!is_gc_adapter_rule(
+            r#"SUBSYSTEM=="usb", ATTRS{idVendor}=="057e", ATTRS{idProduct}=="0306", MODE="0666""#
+        ));
+    }
+
+    #[test]
+    fn finds_rule_in_either_folder() {
+        let root = TempRoot::new("either_folder");
+        assert_eq!(find_gc_adapter_rule(&root.0).unwrap(), None);
+        let path = root.write(
+            RULES_FOLDERS[1],
+            RULE_FILE_NAME,
+            gc_adapter_rule().as_bytes(),
+        );
+        assert_eq!(find_gc_adapter_rule(&root.0).unwrap(), Some(path));
+    }
+
+    #[test]
+    fn commented_or_incomplete_rules_are_not_found() {
+        let root = TempRoot::new("not_found");
+        root.write(
+            RULES_FOLDERS[0],
+            "50-commented.rules",
+            format!("# {}", gc_adapter_rule()).as_bytes(),
+        );
+        root.write(
+            RULES_FOLDERS[0],
+            "51-no-access.rules",
+            br#"SUBSYSTEM=="usb", ATTRS{idVendor}=="057e", ATTRS{idProduct}=="0337""#,
+        );
+        root.write(
+            RULES_FOLDERS[0],
+            "52-gcadapter.txt",
+            gc_adapter_rule().as_bytes(),
+        );
+        assert_eq!(find_gc_adapter_rule(&root.0).unwrap(), None);
+    }
+
+    #[test]
+    fn unreadable_rules_are_skipped() {
+        let root = TempRoot::new("unreadable");
+        // invalid utf-8 can't be read as text, even when running as root
+        root.write(RULES_FOLDERS[0], "50-broken.rules", &[0xff, 0xfe, 0x00]);
+        assert_eq!(find_gc_adapter_rule(&root.0).unwrap(), None);
+        let path = root.write(
+            RULES_FOLDERS[0],
+            RULE_FILE_NAME,
+            gc_adapter_rule().as_bytes(),
+        );
+        assert_eq!(find_gc_adapter_rule(&root.0).unwrap(), Some(path));
+    }
+}
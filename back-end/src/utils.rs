@@ -1,3 +1,11 @@
+use ini::Ini;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
 #[macro_export]
 macro_rules! io_error {
     ($kind:ident, $($arg:tt)*) => {{
@@ -5,3 +13,103 @@ macro_rules! io_error {
     }};
 }
 pub use io_error;
+
+// per-game configs only take effect for keys that opt out of the global value
+pub fn set_per_game_setting(ini: &mut Ini, section: &str, key: &str, value: &str) {
+    ini.with_section(Some(section))
+        .set(format!("{}\\use_global", key), "false")
+        .set(format!("{}\\default", key), "false")
+        .set(key, value);
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct DirStats {
+    pub size: u64,
+    pub file_count: u64,
+    // seconds since the unix epoch
+    pub last_modified: u64,
+}
+
+pub fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// YYYY-MM-DD_HH-MM-SS in UTC, from seconds since the unix epoch
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds = timestamp % 86400;
+    // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+pub fn dir_stats(path: &Path) -> io::Result<DirStats> {
+    let mut stats = DirStats {
+        last_modified: unix_time(fs::metadata(path)?.modified()?),
+        ..Default::default()
+    };
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            let sub_stats = dir_stats(entry.path().as_path())?;
+            stats.size += sub_stats.size;
+            stats.file_count += sub_stats.file_count;
+            stats.last_modified = stats.last_modified.max(sub_stats.last_modified);
+        } else {
+            stats.size += metadata.len();
+            stats.file_count += 1;
+            stats.last_modified = stats.last_modified.max(unix_time(metadata.modified()?));
+        }
+    }
+    Ok(stats)
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    path.with_file_name(temp_name)
+}
+
+// writes next to the target and renames over it so an interrupted write never leaves a partial file
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temp_path = temp_path(path);
+    fs::write(temp_path.as_path(), contents)?;
+    fs::rename(temp_path, path)
+}
+
+pub fn write_atomic_from(path: &Path, reader: &mut impl io::Read) -> io::Result<u64> {
+    let temp_path = temp_path(path);
+    let bytes = io::copy(reader, &mut fs::File::create(temp_path.as_path())?)?;
+    fs::rename(temp_path, path)?;
+    Ok(bytes)
+}
@@ -0,0 +1,310 @@
+use crate::bundled_mods::{self, BundledTree};
+use crate::config::{
+    OptimizerConfig, UserStatus, INPUT_TARGETS, MODS_TARGETS, SAVE_TARGETS, SETTINGS_TARGETS,
+};
+use crate::merge;
+use crate::optimizer;
+use crate::packs::{ContentFile, ContentSources, ContentVersion, PackTarget};
+use crate::profile::UserProfile;
+use crate::utils::io_error;
+use ini::Ini;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+
+// relative to the skyline folder, without these the game boots unmodded or not at all
+static REQUIRED_SKYLINE_FILES: &[&str] = &[
+    "exefs/subsdk9",
+    "exefs/main.npdm",
+    "romfs/skyline/plugins/libarcropolis.nro",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VerificationProblem {
+    Missing,
+    Modified,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationIssue {
+    pub path: PathBuf,
+    pub problem: VerificationProblem,
+}
+
+fn add_issue(issues: &mut Vec<VerificationIssue>, path: PathBuf, problem: VerificationProblem) {
+    log::warn!(
+        "Verification failed, {:?}: {}",
+        problem,
+        path.to_string_lossy()
+    );
+    issues.push(VerificationIssue { path, problem });
+}
+
+// ordered from best to worst, a group of files takes the worst state of any of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum OptimizationState {
+    Optimized,
+    Outdated,
+    Modified,
+    Missing,
+    NotOptimized,
+}
+
+impl From<VerificationProblem> for OptimizationState {
+    fn from(problem: VerificationProblem) -> Self {
+        match problem {
+            VerificationProblem::Missing => OptimizationState::Missing,
+            VerificationProblem::Modified => OptimizationState::Modified,
+        }
+    }
+}
+
+// the recorded status with every optimization checked against what is actually on disk
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifiedUserStatus {
+    #[serde(flatten)]
+    pub status: UserStatus,
+    pub settings_state: OptimizationState,
+    pub mods_state: OptimizationState,
+    pub save_state: OptimizationState,
+    pub input_state: OptimizationState,
+}
+
+fn check_files(
+    issues: &mut Vec<VerificationIssue>,
+    files: &[ContentFile],
+    target: &Path,
+    filter: &dyn Fn(&Path) -> bool,
+) -> io::Result<()> {
+    for file in files.iter().filter(|f| filter(&f.relative_path)) {
+        let path = target.join(&file.relative_path);
+        if !path.is_file() {
+            add_issue(issues, path, VerificationProblem::Missing);
+        } else if !optimizer::is_up_to_date(path.as_path(), file)? {
+            add_issue(issues, path, VerificationProblem::Modified);
+        }
+    }
+    Ok(())
+}
+
+fn issues_state(issues: &[VerificationIssue]) -> OptimizationState {
+    issues
+        .iter()
+        .map(|i| OptimizationState::from(i.problem))
+        .max()
+        .unwrap_or(OptimizationState::Optimized)
+}
+
+// checks that the installed mod environment would actually boot after optimize_mods
+pub fn verify_mods(
+    config: &OptimizerConfig,
+    sources: &ContentSources,
+    user_profile: &UserProfile,
+    selected_mods: &[String],
+) -> io::Result<Vec<VerificationIssue>> {
+    let skyline_path = config.get_skyline_folder()?;
+    let arc_mods_path = config.get_arc_mods_folder()?;
+    let mut issues = vec![];
+    for file in REQUIRED_SKYLINE_FILES {
+        let path = skyline_path.join(file);
+        if !path.is_file() {
+            add_issue(&mut issues, path, VerificationProblem::Missing);
+        }
+    }
+
+    // the arcropolis config is left out, it is meant to be edited by the user
+    check_files(
+        &mut issues,
+        &sources.files(PackTarget::SdmcSkyline)?,
+        &skyline_path,
+        &|p| bundled_mods::is_path_selected(BundledTree::Skyline, p, selected_mods),
+    )?;
+    check_files(
+        &mut issues,
+        &sources.files(PackTarget::ArcMods)?,
+        &arc_mods_path,
+        &|p| bundled_mods::is_path_selected(BundledTree::ArcMods, p, selected_mods),
+    )?;
+
+    let arc_config_path = config.get_arc_config_folder(user_profile)?;
+    if !arc_config_path.is_dir() {
+        add_issue(&mut issues, arc_config_path, VerificationProblem::Missing);
+    }
+    Ok(issues)
+}
+
+fn settings_state(
+    config: &OptimizerConfig,
+    sources: &ContentSources,
+) -> io::Result<OptimizationState> {
+    let ssbu_settings_path = config
+        .emu_filesystem
+        .config_folder
+        .as_ref()
+        .ok_or(io_error!(NotFound, "Emulator config folder not found"))?
+        .join("custom");
+    let settings_base_path = config.get_settings_base_folder();
+    let mut state = OptimizationState::Optimized;
+    for file in sources.files(PackTarget::ConfigCustom)?.iter() {
+        let file_name = file.file_name();
+        let target = ssbu_settings_path.join(&file_name);
+        if !target.is_file() {
+            return Ok(OptimizationState::Missing);
+        }
+        let current = Ini::load_from_file_noescape(target.as_path()).map_err(|e| {
+            io_error!(
+                InvalidData,
+                "Unable to parse settings file {}: {}",
+                target.to_string_lossy(),
+                e
+            )
+        })?;
+        let bundled = optimizer::load_bundled_ini(file)?;
+        // the base holds the values as last applied, including the preset and hardware tuning
+        let base = Ini::load_from_file_noescape(settings_base_path.join(&file_name)).ok();
+        let applied = base.as_ref().unwrap_or(&bundled);
+
+        let applied_keys = merge::ini_keys(applied);
+        if applied_keys
+            .iter()
+            .any(|k| merge::ini_value(&current, k) != merge::ini_value(applied, k))
+        {
+            state = state.max(OptimizationState::Modified);
+        } else if merge::ini_keys(&bundled)
+            .iter()
+            .any(|k| !applied_keys.contains(k))
+        {
+            state = state.max(OptimizationState::Outdated);
+        }
+    }
+    Ok(state)
+}
+
+fn mods_state(
+    config: &OptimizerConfig,
+    sources: &ContentSources,
+    user_profile: &UserProfile,
+    status: &UserStatus,
+) -> io::Result<OptimizationState> {
+    Ok(issues_state(&verify_mods(
+        config,
+        sources,
+        user_profile,
+        &status.installed_mods,
+    )?))
+}
+
+fn save_state(
+    config: &OptimizerConfig,
+    sources: &ContentSources,
+    user_profile: &UserProfile,
+) -> io::Result<OptimizationState> {
+    let mut issues = vec![];
+    check_files(
+        &mut issues,
+        &sources.files(PackTarget::Save)?,
+        &config.get_save_folder(user_profile)?,
+        &|_| true,
+    )?;
+    Ok(issues_state(&issues))
+}
+
+fn input_state(
+    config: &OptimizerConfig,
+    sources: &ContentSources,
+    status: &UserStatus,
+) -> io::Result<OptimizationState> {
+    let mut issues = vec![];
+    check_files(
+        &mut issues,
+        &sources.files(PackTarget::ConfigInput)?,
+        &config.get_input_profile_folder()?,
+        &|_| true,
+    )?;
+    let profile = match &status.input_profile {
+        Some(profile) => profile,
+        None => return Ok(issues_state(&issues)),
+    };
+    let game_settings_path = config.get_game_settings_path()?;
+    let bound = Ini::load_from_file_noescape(game_settings_path.as_path())
+        .ok()
+        .and_then(|i| {
+            i.get_from(Some("Controls"), "player_0_profile_name")
+                .map(String::from)
+        });
+    if bound.as_ref() != Some(profile) {
+        add_issue(
+            &mut issues,
+            game_settings_path,
+            VerificationProblem::Modified,
+        );
+    }
+    Ok(issues_state(&issues))
+}
+
+pub fn verify_user_status(
+    config: &OptimizerConfig,
+    user_profile: &UserProfile,
+) -> VerifiedUserStatus {
+    let sources = ContentSources::load(config);
+    let mut status = config
+        .local_data
+        .user_statuses
+        .get(user_profile)
+        .cloned()
+        .unwrap_or_default();
+    // optimizations applied before versions were recorded are outdated too
+    let check = |optimized: bool,
+                 targets: &[PackTarget],
+                 version: &Option<ContentVersion>,
+                 state: io::Result<OptimizationState>| {
+        if !optimized {
+            return OptimizationState::NotOptimized;
+        }
+        let state = state.unwrap_or_else(|e| {
+            log::warn!("Unable to verify {:?}: {}", targets, e);
+            OptimizationState::Missing
+        });
+        // files installed from older content no longer match the current one, so they show as outdated
+        match version {
+            Some(v) if v.content_hash == sources.version(targets).content_hash => state,
+            _ if state == OptimizationState::Missing => state,
+            _ => OptimizationState::Outdated,
+        }
+    };
+    let settings_state = check(
+        status.settings_optimized,
+        SETTINGS_TARGETS,
+        &status.settings_version,
+        settings_state(config, &sources),
+    );
+    let mods_state = check(
+        status.mods_optimized,
+        MODS_TARGETS,
+        &status.mods_version,
+        mods_state(config, &sources, user_profile, &status),
+    );
+    let save_state = check(
+        status.save_optimized,
+        SAVE_TARGETS,
+        &status.save_version,
+        save_state(config, &sources, user_profile),
+    );
+    let input_state = check(
+        status.input_optimized,
+        INPUT_TARGETS,
+        &status.input_version,
+        input_state(config, &sources, &status),
+    );
+    status.settings_optimized = settings_state == OptimizationState::Optimized;
+    status.mods_optimized = mods_state == OptimizationState::Optimized;
+    status.save_optimized = save_state == OptimizationState::Optimized;
+    status.input_optimized = input_state == OptimizationState::Optimized;
+    VerifiedUserStatus {
+        status,
+        settings_state,
+        mods_state,
+        save_state,
+        input_state,
+    }
+}
@@ -0,0 +1,179 @@
+use crate::config::OptimizerConfig;
+use crate::profile::UserProfile;
+use crate::utils::io_error;
+use ini::{Ini, Properties};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const WEB_SERVICE_SECTION: &str = "WebService";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebServiceServer {
+    pub name: String,
+    pub web_api_url: String,
+    // the selected user profile name is used when no username is set
+    pub username: Option<String>,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebServiceSettings {
+    pub enabled: bool,
+    pub disable_telemetry: bool,
+    pub selected_server: usize,
+    pub servers: Vec<WebServiceServer>,
+}
+
+impl Default for WebServiceSettings {
+    fn default() -> Self {
+        WebServiceSettings {
+            enabled: true,
+            disable_telemetry: true,
+            selected_server: 0,
+            servers: vec![WebServiceServer {
+                name: String::from("ynet-fun"),
+                web_api_url: String::from("api.ynet-fun.xyz"),
+                username: None,
+                token: String::from("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"),
+            }],
+        }
+    }
+}
+
+fn get_main_config_path(config: &OptimizerConfig) -> io::Result<PathBuf> {
+    Ok(config
+        .emu_filesystem
+        .config_folder
+        .as_ref()
+        .ok_or(io_error!(NotFound, "Emulator config folder not found"))?
+        .join("qt-config.ini"))
+}
+
+fn get_backup_path(config: &OptimizerConfig) -> PathBuf {
+    config
+        .app_data_folder
+        .join("web_service_backup")
+        .join(format!("{}.ini", config.get_emulator_name()))
+}
+
+fn load_main_config(config: &OptimizerConfig) -> io::Result<(Ini, PathBuf)> {
+    let main_config_settings_path = get_main_config_path(config)?;
+    let main_config = Ini::load_from_file_noescape(main_config_settings_path.as_path())
+        .ok()
+        .ok_or(io_error!(NotFound, "Unable to load main config"))?;
+    Ok((main_config, main_config_settings_path))
+}
+
+fn save_main_config(main_config: &Ini, main_config_settings_path: PathBuf) -> io::Result<()> {
+    main_config
+        .write_to_file_policy(
+            main_config_settings_path.as_path(),
+            ini::EscapePolicy::Nothing,
+        )
+        .ok()
+        .ok_or(io_error!(NotFound, "Unable to save main config settings"))
+}
+
+// only the first backup is kept so re-applying never overwrites the user's original values
+fn backup_web_service(config: &OptimizerConfig, main_config: &Ini) -> io::Result<()> {
+    let backup_path = get_backup_path(config);
+    if backup_path.is_file() {
+        return Ok(());
+    }
+    let section = main_config
+        .section(Some(WEB_SERVICE_SECTION))
+        .ok_or(io_error!(
+            NotFound,
+            "Unable to find WebService section in config"
+        ))?;
+    let mut backup = Ini::new();
+    for (key, value) in section.iter() {
+        backup
+            .with_section(Some(WEB_SERVICE_SECTION))
+            .set(key, value);
+    }
+    log::info!(
+        "Backing up WebService settings to: {}",
+        backup_path.to_string_lossy()
+    );
+    if let Some(parent) = backup_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    backup
+        .write_to_file_policy(backup_path.as_path(), ini::EscapePolicy::Nothing)
+        .ok()
+        .ok_or(io_error!(NotFound, "Unable to save WebService backup"))
+}
+
+pub fn apply_web_service(
+    config: &OptimizerConfig,
+    user_profile: &UserProfile,
+) -> io::Result<Option<String>> {
+    let settings = &config.local_data.web_service;
+    if !settings.enabled {
+        log::info!("WebService step disabled, leaving WebService settings untouched");
+        return Ok(None);
+    }
+    let server = settings
+        .servers
+        .get(settings.selected_server)
+        .ok_or(io_error!(
+            InvalidInput,
+            "Selected WebService server does not exist: {}",
+            settings.selected_server
+        ))?;
+    let (mut main_config, main_config_settings_path) = load_main_config(config)?;
+    backup_web_service(config, &main_config)?;
+
+    log::info!("Applying WebService server: {}", server.name);
+    let section = main_config
+        .section_mut(Some(WEB_SERVICE_SECTION))
+        .ok_or(io_error!(
+            NotFound,
+            "Unable to find WebService section in config"
+        ))?;
+    let emu_name = config.get_emulator_name();
+    if settings.disable_telemetry {
+        section.insert("enable_telemetry\\default", "false");
+        section.insert("enable_telemetry", "false");
+    }
+    section.insert("web_api_url\\default", "false");
+    section.insert("web_api_url", server.web_api_url.as_str());
+    section.insert(format!("{}_username\\default", emu_name), "false");
+    section.insert(
+        format!("{}_username", emu_name),
+        server
+            .username
+            .as_ref()
+            .unwrap_or(&user_profile.name)
+            .as_str(),
+    );
+    section.insert(format!("{}_token\\default", emu_name), "false");
+    section.insert(format!("{}_token", emu_name), server.token.as_str());
+    save_main_config(&main_config, main_config_settings_path)?;
+    Ok(Some(server.name.clone()))
+}
+
+pub fn restore_web_service(config: &OptimizerConfig) -> io::Result<()> {
+    let backup_path = get_backup_path(config);
+    let backup = Ini::load_from_file_noescape(backup_path.as_path())
+        .ok()
+        .ok_or(io_error!(NotFound, "No WebService backup found to restore"))?;
+    let (mut main_config, main_config_settings_path) = load_main_config(config)?;
+
+    log::info!(
+        "Restoring WebService settings from: {}",
+        backup_path.to_string_lossy()
+    );
+    let backup_section = backup
+        .section(Some(WEB_SERVICE_SECTION))
+        .cloned()
+        .unwrap_or_default();
+    *main_config
+        .entry(Some(WEB_SERVICE_SECTION.to_string()))
+        .or_insert(Properties::new()) = backup_section;
+    save_main_config(&main_config, main_config_settings_path)?;
+    fs::remove_file(backup_path)
+}